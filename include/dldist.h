@@ -0,0 +1,41 @@
+/* C interface of the dldist library, built with `cargo build --release --features capi`. */
+#ifndef DLDIST_H
+#define DLDIST_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DLDIST_OK 0
+#define DLDIST_INVALID_ARGUMENT -1
+
+/* Distance of a pair of lines, which are referred to by their 0-based indices. */
+typedef struct DldistPair {
+    uint64_t line_a;
+    uint64_t line_b;
+    uint32_t dist;
+    /* Distance divided by the mean length of both lines. */
+    float norm;
+} DldistPair;
+
+/* Returns the optimal string alignment distance of both NUL-terminated strings, compared byte-wise. */
+uint32_t dldist_osa(const char *a, const char *b);
+
+/* Calculates the distances of all pairs of the `lines_cnt` lines, each line only paired with the `window` lines
+ * following it (0 pairs all lines), with `threads` threads. On success `*out_pairs` points to the `*out_cnt` pairs
+ * sorted ascending by their distance, which have to be released with dldist_pairs_free(). Safe to call from several
+ * threads concurrently. */
+int dldist_pairwise(const char *const *lines, size_t lines_cnt, size_t window, size_t threads,
+                    DldistPair **out_pairs, size_t *out_cnt);
+
+/* Releases pairs returned by dldist_pairwise(). */
+void dldist_pairs_free(DldistPair *pairs, size_t cnt);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DLDIST_H */
@@ -0,0 +1,105 @@
+//! Reading of the members of zip and tar archives given as input files, which are detected by their extension. A
+//! single member can be selected by appending its path within the archive, e.g. `bundle.zip:inner/file.txt`. Tar
+//! archives may be compressed with gzip.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+#[derive(Clone, Copy)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+const EXTENSIONS: &[(&str, ArchiveKind)] = &[
+    (".zip", ArchiveKind::Zip),
+    (".tar", ArchiveKind::Tar),
+    (".tar.gz", ArchiveKind::TarGz),
+    (".tgz", ArchiveKind::TarGz),
+];
+
+/// Input file naming an archive, along with the member to read if only a single one is selected.
+pub struct ArchiveInput {
+    pub path: PathBuf,
+    member: Option<String>,
+    kind: ArchiveKind,
+}
+
+impl ArchiveInput {
+    /// Returns the archive named by the input path, if it is one.
+    pub fn parse(input: &Path) -> Option<Self> {
+        let input = input.to_str()?;
+        let lowercase = input.to_ascii_lowercase();
+        EXTENSIONS.iter().find_map(|&(extension, kind)| {
+            // the member is separated by a colon right after the extension of the archive
+            let end = match lowercase.find(&format!("{extension}:")) {
+                Some(start) => start + extension.len(),
+                None if lowercase.ends_with(extension) => input.len(),
+                None => return None,
+            };
+            Some(ArchiveInput {
+                path: PathBuf::from(&input[..end]),
+                member: input.get(end + 1..).map(str::to_string),
+                kind,
+            })
+        })
+    }
+
+    /// Reads in the regular files of the archive (or only the selected one) in the order they are stored, as pairs
+    /// of their path within the archive and their content.
+    pub fn read_members(&self) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let file = BufReader::new(File::open(&self.path)?);
+        let members = match self.kind {
+            ArchiveKind::Zip => self.read_zip_members(file)?,
+            ArchiveKind::Tar => self.read_tar_members(file)?,
+            ArchiveKind::TarGz => self.read_tar_members(GzDecoder::new(file))?,
+        };
+        if let (Some(member), true) = (&self.member, members.is_empty()) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("'{}' is not a file within the archive", member),
+            ));
+        }
+        Ok(members)
+    }
+
+    fn is_selected(&self, name: &str) -> bool {
+        self.member.as_ref().is_none_or(|member| member == name)
+    }
+
+    fn read_zip_members(&self, file: BufReader<File>) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut archive = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+        let mut members = Vec::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).map_err(io::Error::other)?;
+            if !entry.is_file() || !self.is_selected(entry.name()) {
+                continue;
+            }
+            let name = entry.name().to_string();
+            let mut content = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut content)?;
+            members.push((name, content));
+        }
+        Ok(members)
+    }
+
+    fn read_tar_members<R: Read>(&self, reader: R) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut archive = tar::Archive::new(reader);
+        let mut members = Vec::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            if !entry.header().entry_type().is_file() || !self.is_selected(&name) {
+                continue;
+            }
+            let mut content = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut content)?;
+            members.push((name, content));
+        }
+        Ok(members)
+    }
+}
@@ -0,0 +1,120 @@
+//! Built-in benchmark timing the distance calculation on synthetic lines for different algorithms and thread counts,
+//! which helps picking the settings for a real run on the given hardware.
+
+use std::num::NonZero;
+use std::thread;
+use std::time::Instant;
+
+use clap::Args;
+
+use dldist::engine::{calculate_osa_distances, pair_combinations_count, window_pairs, THREAD_NUM};
+use dldist::osa::{Algorithm, Kernel, ALGORITHM, KERNEL};
+use dldist::simd;
+
+#[derive(Args)]
+pub struct BenchArguments {
+    /// Amount of synthetic lines to compare with each other.
+    #[arg(short = 'l', long, default_value_t = 1000)]
+    lines: usize,
+
+    /// Mean length of the synthetic lines in bytes. The actual lengths vary by up to 25%.
+    #[arg(long, default_value_t = 80)]
+    line_len: usize,
+
+    /// Comma-separated thread counts to time. Defaults to powers of two up to all-but-one available cores.
+    #[arg(short = 'j', long, value_delimiter = ',')]
+    thread_nums: Vec<NonZero<usize>>,
+
+    /// Seed for generating the synthetic lines, to compare the results of different machines.
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+}
+
+/// Algorithms available for timing, along with the kernel implementing them.
+const ALGORITHMS: &[(&str, Algorithm, Kernel)] = &[
+    ("osa", Algorithm::Osa, Kernel::Scalar),
+    ("osa-simd", Algorithm::Osa, Kernel::Simd),
+    ("token-set", Algorithm::TokenSet, Kernel::Scalar),
+];
+
+/// Minimal xorshift generator, as the synthetic lines neither need to be cryptographically secure nor high-quality.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Returns `cnt` lines of printable ASCII characters with lengths around `mean_len`.
+fn generate_lines(cnt: usize, mean_len: usize, seed: u64) -> Vec<Vec<u8>> {
+    // zero is the only state xorshift can not leave
+    let mut rng = XorShift(seed.max(1));
+    let len_variance = mean_len / 4;
+    (0..cnt)
+        .map(|_| {
+            let len = mean_len - len_variance + (rng.next() as usize) % (2 * len_variance + 1);
+            (0..len).map(|_| b' ' + (rng.next() % 95) as u8).collect()
+        })
+        .collect()
+}
+
+fn default_thread_nums() -> Vec<NonZero<usize>> {
+    let max_threads = thread::available_parallelism()
+        .map(|n| n.get().saturating_sub(1).max(1))
+        .unwrap_or(1);
+    let mut thread_nums: Vec<_> = (0..)
+        .map(|exp| 1usize << exp)
+        .take_while(|&n| n < max_threads)
+        .collect();
+    thread_nums.push(max_threads);
+    thread_nums.into_iter().filter_map(NonZero::new).collect()
+}
+
+pub fn run(args: &BenchArguments) {
+    let thread_nums = if args.thread_nums.is_empty() {
+        default_thread_nums()
+    } else {
+        args.thread_nums.clone()
+    };
+    let lines = generate_lines(args.lines, args.line_len, args.seed);
+    let pairs_cnt = pair_combinations_count(lines.len() as u64);
+    println!(
+        "==> Benchmarking {} pairs of {} synthetic lines with a mean length of {}...",
+        pairs_cnt,
+        lines.len(),
+        args.line_len
+    );
+
+    println!(
+        "{: <10} {: >7} {: >10} {: >12} {: >8}",
+        "algorithm", "threads", "time [s]", "pairs/s", "speedup"
+    );
+    for &(name, algorithm, kernel) in ALGORITHMS {
+        if kernel == Kernel::Simd && !simd::supported() {
+            continue;
+        }
+        *ALGORITHM.lock().unwrap() = algorithm;
+        *KERNEL.lock().unwrap() = kernel;
+        let mut single_thread_secs = None;
+        for thread_num in &thread_nums {
+            *THREAD_NUM.lock().unwrap() = thread_num.get();
+            let start_time = Instant::now();
+            calculate_osa_distances(&lines, &lines, window_pairs(lines.len(), lines.len()));
+            let secs = start_time.elapsed().as_secs_f64();
+            // the speedup refers to the first timed thread count, which is single-threaded by default
+            let base_secs = *single_thread_secs.get_or_insert(secs);
+            println!(
+                "{: <10} {: >7} {: >10.4} {: >12.0} {: >7.2}x",
+                name,
+                thread_num,
+                secs,
+                pairs_cnt as f64 / secs,
+                base_secs / secs
+            );
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! C interface of the distance and pairwise engine, declared in `include/dldist.h`.
+//!
+//! Strings are NUL-terminated and compared byte-wise. Pairs returned by [`dldist_pairwise`] are owned by the library
+//! and have to be released with [`dldist_pairs_free`].
+
+use std::ffi::{c_char, c_int, CStr};
+use std::ptr;
+use std::slice;
+
+use crate::engine::{
+    calculate_osa_distances_with, sort_distance_results, window_pairs, Normalization, Settings,
+};
+use crate::osa::{calculate_distance_with, DistanceSettings};
+
+const DLDIST_OK: c_int = 0;
+const DLDIST_INVALID_ARGUMENT: c_int = -1;
+
+/// Distance of a pair of lines, which are referred to by their 0-based indices.
+#[repr(C)]
+pub struct DldistPair {
+    pub line_a: u64,
+    pub line_b: u64,
+    pub dist: u32,
+    /// Distance divided by the mean length of both lines.
+    pub norm: f32,
+}
+
+/// Returns the optimal string alignment distance of both strings.
+///
+/// # Safety
+///
+/// Both pointers have to point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn dldist_osa(a: *const c_char, b: *const c_char) -> u32 {
+    let (a, b) = unsafe { (CStr::from_ptr(a), CStr::from_ptr(b)) };
+    calculate_distance_with(a.to_bytes(), b.to_bytes(), &DistanceSettings::default())
+}
+
+/// Calculates the distances of all pairs of the `lines_cnt` lines, each line only paired with the `window` lines
+/// following it (0 pairs all lines), with `threads` threads. On success `*out_pairs` points to the `*out_cnt` pairs
+/// sorted ascending by their distance and `DLDIST_OK` is returned, otherwise `DLDIST_INVALID_ARGUMENT`. It is safe to
+/// call from several threads concurrently.
+///
+/// # Safety
+///
+/// `lines` has to point to `lines_cnt` NUL-terminated strings, `out_pairs` and `out_cnt` have to be writable.
+#[no_mangle]
+pub unsafe extern "C" fn dldist_pairwise(
+    lines: *const *const c_char,
+    lines_cnt: usize,
+    window: usize,
+    threads: usize,
+    out_pairs: *mut *mut DldistPair,
+    out_cnt: *mut usize,
+) -> c_int {
+    if (lines.is_null() && lines_cnt > 0)
+        || out_pairs.is_null()
+        || out_cnt.is_null()
+        || threads == 0
+    {
+        return DLDIST_INVALID_ARGUMENT;
+    }
+    let lines: Vec<Vec<u8>> = if lines_cnt == 0 {
+        Vec::new()
+    } else {
+        let line_ptrs = unsafe { slice::from_raw_parts(lines, lines_cnt) };
+        if line_ptrs.iter().any(|p| p.is_null()) {
+            return DLDIST_INVALID_ARGUMENT;
+        }
+        line_ptrs
+            .iter()
+            .map(|&p| unsafe { CStr::from_ptr(p) }.to_bytes().to_vec())
+            .collect()
+    };
+    let window = if window == 0 { lines.len() } else { window };
+
+    // the settings of the call are passed along instead of set globally, as other threads might call concurrently
+    let settings = Settings {
+        thread_num: threads,
+        normalization: Normalization::Mean,
+        as_similarity: false,
+        distance: DistanceSettings::default(),
+    };
+    let mut results =
+        calculate_osa_distances_with(&lines, &lines, window_pairs(lines.len(), window), &settings);
+    sort_distance_results(&mut results, false, false, &settings);
+
+    let pairs: Box<[DldistPair]> = results
+        .iter()
+        .map(|dr| DldistPair {
+            line_a: dr.line_a,
+            line_b: dr.line_b,
+            dist: dr.dldist,
+            norm: dr.normalized_dldist,
+        })
+        .collect();
+    unsafe {
+        *out_cnt = pairs.len();
+        *out_pairs = Box::into_raw(pairs).cast();
+    }
+    DLDIST_OK
+}
+
+/// Releases pairs returned by [`dldist_pairwise`]. Null pointers are ignored.
+///
+/// # Safety
+///
+/// `pairs` and `cnt` have to be exactly as returned by [`dldist_pairwise`] and must not be released twice.
+#[no_mangle]
+pub unsafe extern "C" fn dldist_pairs_free(pairs: *mut DldistPair, cnt: usize) {
+    if pairs.is_null() {
+        return;
+    }
+    drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(pairs, cnt)) });
+}
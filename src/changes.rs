@@ -0,0 +1,124 @@
+//! Classification of the lines of a file compared to the lines of an older revision of it, see `--git-base`.
+
+use dldist::engine::DistanceResult;
+
+/// What happened to a current line since the old revision.
+#[derive(Clone, Copy)]
+pub enum LineChange<'a> {
+    /// Equal to the old line at the same position.
+    Unchanged,
+    /// Equal to the old line `line_b` of the result at another position.
+    Moved(&'a DistanceResult),
+    /// Edited from the old line `line_b` of the result.
+    Mutated(&'a DistanceResult),
+    /// Without an old counterpart.
+    Added,
+}
+
+/// Returns whether the current line is rather an edit of the old line than a replacement of it, which is the case if
+/// at most half of the longer of both lines has been edited.
+fn is_mutation(
+    dr: &DistanceResult,
+    line_len: usize,
+    base_line_len: usize,
+    edit_cost_unit: u32,
+) -> bool {
+    dr.dldist as u64 * 2 <= edit_cost_unit as u64 * line_len.max(base_line_len) as u64
+}
+
+/// Pairs every current line with at most one old line, given the results of all pairs of a current line (`line_a`)
+/// and an old line (`line_b`). The closest pairs are taken first, preferring nearby positions on equal distances.
+/// Returns the change of every current line along with the indices of the old lines left without a counterpart,
+/// which have been deleted.
+pub fn classify_line_changes<'a>(
+    results: &'a [DistanceResult],
+    lines: &[Vec<u8>],
+    base_lines: &[Vec<u8>],
+    edit_cost_unit: u32,
+) -> (Vec<LineChange<'a>>, Vec<usize>) {
+    let mut candidates: Vec<&DistanceResult> = results
+        .iter()
+        .filter(|dr| {
+            let (la, lb) = (dr.line_a as usize, dr.line_b as usize);
+            is_mutation(dr, lines[la].len(), base_lines[lb].len(), edit_cost_unit)
+        })
+        .collect();
+    candidates.sort_by_key(|dr| {
+        (
+            dr.dldist,
+            dr.line_a.abs_diff(dr.line_b),
+            dr.line_a,
+            dr.line_b,
+        )
+    });
+
+    let mut changes = vec![LineChange::Added; lines.len()];
+    let mut base_matched = vec![false; base_lines.len()];
+    for dr in candidates {
+        let (la, lb) = (dr.line_a as usize, dr.line_b as usize);
+        if !matches!(changes[la], LineChange::Added) || base_matched[lb] {
+            continue;
+        }
+        base_matched[lb] = true;
+        changes[la] = match (dr.dldist, la == lb) {
+            (0, true) => LineChange::Unchanged,
+            (0, false) => LineChange::Moved(dr),
+            _ => LineChange::Mutated(dr),
+        };
+    }
+    let deleted = (0..base_lines.len())
+        .filter(|&lb| !base_matched[lb])
+        .collect();
+    (changes, deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dldist::engine::{calculate_osa_distances, cross_pairs};
+
+    fn to_lines(texts: &[&str]) -> Vec<Vec<u8>> {
+        texts.iter().map(|text| text.as_bytes().to_vec()).collect()
+    }
+
+    fn results(lines: &[Vec<u8>], base_lines: &[Vec<u8>]) -> Vec<DistanceResult> {
+        calculate_osa_distances(
+            lines,
+            base_lines,
+            cross_pairs(lines.len(), base_lines.len()),
+        )
+    }
+
+    #[test]
+    fn appended_line_is_added() {
+        let lines = to_lines(&["first line", "second line", "appended at the end"]);
+        let base_lines = to_lines(&["first line", "second line"]);
+        let results = results(&lines, &base_lines);
+        let (changes, deleted) = classify_line_changes(&results, &lines, &base_lines, 1);
+        assert!(matches!(changes[0], LineChange::Unchanged));
+        assert!(matches!(changes[1], LineChange::Unchanged));
+        assert!(matches!(changes[2], LineChange::Added));
+        assert!(deleted.is_empty());
+    }
+
+    #[test]
+    fn removed_line_is_deleted() {
+        let lines = to_lines(&["first line", "third line"]);
+        let base_lines = to_lines(&["first line", "removed in between", "third line"]);
+        let results = results(&lines, &base_lines);
+        let (changes, deleted) = classify_line_changes(&results, &lines, &base_lines, 1);
+        assert!(matches!(changes[0], LineChange::Unchanged));
+        assert!(matches!(changes[1], LineChange::Moved(dr) if dr.line_b == 2));
+        assert_eq!(deleted, vec![1]);
+    }
+
+    #[test]
+    fn edited_line_is_mutated() {
+        let lines = to_lines(&["first line", "second lime"]);
+        let base_lines = to_lines(&["first line", "second line"]);
+        let results = results(&lines, &base_lines);
+        let (changes, deleted) = classify_line_changes(&results, &lines, &base_lines, 1);
+        assert!(matches!(changes[1], LineChange::Mutated(dr) if dr.line_b == 1 && dr.dldist == 1));
+        assert!(deleted.is_empty());
+    }
+}
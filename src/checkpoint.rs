@@ -0,0 +1,268 @@
+//! Persisting of completed pairs of long runs, so that they can be resumed after being aborted.
+//!
+//! A checkpoint file starts with a header identifying the input and is followed by batches of results, each
+//! terminated by a marker line:
+//! ```text
+//! dldist-checkpoint v1 <fingerprint>
+//! <line_a> <line_b> <dldist>
+//! ...
+//! done
+//! ```
+//! Since the pairs are always scheduled in the same order, the amount of stored results tells which pairs are done.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use dldist::engine::{DistanceResult, Settings};
+use dldist::osa::{Algorithm, TypoModel};
+
+use crate::ignore::{content_hash, extend_hash};
+
+/// Amount of pairs that are calculated in between writing checkpoints.
+const BATCH_SIZE: usize = 100_000;
+const HEADER_PREFIX: &str = "dldist-checkpoint v1";
+const BATCH_END_MARKER: &str = "done";
+
+/// Returns a fingerprint of the compared lines and the settings determining the pairs and their distances, so that a
+/// checkpoint is only resumed by the same run. The FNV-1a hash keeps the fingerprint stable across builds.
+pub fn input_fingerprint(
+    lines: &[Vec<u8>],
+    window: usize,
+    focus_lines: &[usize],
+    algorithm: Algorithm,
+    typo_model: Option<TypoModel>,
+    transposition_cost: Option<u32>,
+) -> u64 {
+    let settings = format!(
+        "window={} focus-lines={:?} algorithm={} typo-model={} transpose-cost={:?}",
+        window,
+        focus_lines,
+        algorithm.to_possible_value().unwrap().get_name(),
+        typo_model
+            .and_then(|m| m.to_possible_value())
+            .map_or_else(|| "none".to_string(), |v| v.get_name().to_string()),
+        transposition_cost
+    );
+    lines
+        .iter()
+        .fold(content_hash(settings.as_bytes()), |hash, line| {
+            // the lengths keep the boundaries of the lines apart
+            extend_hash(extend_hash(hash, &(line.len() as u64).to_le_bytes()), line)
+        })
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Reads in the results stored in a checkpoint file. Results of a batch which has not been completely written are
+/// dropped, as they will be calculated again.
+pub fn read(path: &Path, fingerprint: u64, lines: &[Vec<u8>]) -> io::Result<Vec<DistanceResult>> {
+    let mut file_lines = io::BufReader::new(File::open(path)?).lines();
+    let header = file_lines.next().transpose()?.unwrap_or_default();
+    if header != format!("{} {:016x}", HEADER_PREFIX, fingerprint) {
+        return Err(invalid_data(
+            "checkpoint has been written for a different input or settings".to_string(),
+        ));
+    }
+
+    let settings = Settings::current();
+    let mut results = Vec::new();
+    let mut batch = Vec::new();
+    for file_line in file_lines {
+        let file_line = file_line?;
+        if file_line == BATCH_END_MARKER {
+            results.append(&mut batch);
+            continue;
+        }
+        let values: Vec<u64> = match file_line
+            .split(' ')
+            .map(|v| v.parse::<u64>())
+            .collect::<Result<_, _>>()
+        {
+            Ok(vals) => vals,
+            // only a partially written last line can not be parsed
+            Err(_) => break,
+        };
+        if values.len() != 3 {
+            break;
+        }
+        let (la, lb) = (values[0] as usize, values[1] as usize);
+        if la >= lines.len() || lb >= lines.len() {
+            return Err(invalid_data(format!(
+                "invalid pair in checkpoint: {}",
+                file_line
+            )));
+        }
+        batch.push(DistanceResult::new(
+            values[0],
+            values[1],
+            lines[la].len(),
+            lines[lb].len(),
+            values[2] as u32,
+            &settings,
+        ));
+    }
+    Ok(results)
+}
+
+fn append_batch(file: &mut File, results: &[DistanceResult]) -> io::Result<()> {
+    let mut writer = BufWriter::new(&mut *file);
+    for dr in results {
+        writeln!(writer, "{} {} {}", dr.line_a, dr.line_b, dr.dldist)?;
+    }
+    writeln!(writer, "{}", BATCH_END_MARKER)?;
+    writer.flush()?;
+    drop(writer);
+    file.sync_data()
+}
+
+/// Calculates the distances of the given pairs in batches and appends the results of each batch to the checkpoint
+/// file. The pairs already covered by `done_results` are skipped.
+pub fn calculate_osa_distances<I>(
+    path: &Path,
+    fingerprint: u64,
+    lines: &[Vec<u8>],
+    pairs: I,
+    done_results: Vec<DistanceResult>,
+) -> io::Result<Vec<DistanceResult>>
+where
+    I: Iterator<Item = (usize, usize)>,
+{
+    // rewriting the readable part only drops a partially written batch of an aborted run, it is written next to the
+    // old checkpoint and renamed, so that an aborted rewrite keeps the old one
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut file = File::create(&tmp_path)?;
+    writeln!(file, "{} {:016x}", HEADER_PREFIX, fingerprint)?;
+    if done_results.is_empty() {
+        file.sync_data()?;
+    } else {
+        append_batch(&mut file, &done_results)?;
+    }
+    drop(file);
+    fs::rename(&tmp_path, path)?;
+    let mut file = OpenOptions::new().append(true).open(path)?;
+
+    let mut pairs = pairs.skip(done_results.len());
+    let mut results = done_results;
+    loop {
+        let batch =
+            dldist::engine::calculate_osa_distances(lines, lines, pairs.by_ref().take(BATCH_SIZE));
+        if batch.is_empty() {
+            break;
+        }
+        append_batch(&mut file, &batch)?;
+        results.extend(batch);
+        tracing::info!(done = results.len(), "wrote checkpoint");
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dldist::engine::window_pairs;
+
+    fn to_lines(texts: &[&str]) -> Vec<Vec<u8>> {
+        texts.iter().map(|text| text.as_bytes().to_vec()).collect()
+    }
+
+    fn fingerprint(lines: &[Vec<u8>], algorithm: Algorithm) -> u64 {
+        input_fingerprint(lines, lines.len(), &[], algorithm, None, None)
+    }
+
+    fn distances(results: &[DistanceResult]) -> Vec<(u64, u64, u32)> {
+        results
+            .iter()
+            .map(|dr| (dr.line_a, dr.line_b, dr.dldist))
+            .collect()
+    }
+
+    #[test]
+    fn resuming_drops_the_partial_batch_and_completes_the_pairs() {
+        let path = std::env::temp_dir().join(format!("dldist-checkpoint-{}", std::process::id()));
+        let lines = to_lines(&["kitten", "sitting", "mitten", "fitting", "smitten"]);
+        let fp = fingerprint(&lines, Algorithm::Osa);
+        let expected = dldist::engine::calculate_osa_distances(
+            &lines,
+            &lines,
+            window_pairs(lines.len(), lines.len()),
+        );
+
+        // an aborted run with a completed batch of 4 pairs and a partially written one
+        let mut content = format!("{} {:016x}\n", HEADER_PREFIX, fp);
+        for dr in &expected[..4] {
+            content += &format!("{} {} {}\n", dr.line_a, dr.line_b, dr.dldist);
+        }
+        content += "done\n";
+        let dr = &expected[4];
+        content += &format!("{} {} {}\n{} {}", dr.line_a, dr.line_b, dr.dldist, 1, 3);
+        fs::write(&path, content).unwrap();
+
+        let done = read(&path, fp, &lines).unwrap();
+        assert_eq!(distances(&done), distances(&expected[..4]));
+        let results = calculate_osa_distances(
+            &path,
+            fp,
+            &lines,
+            window_pairs(lines.len(), lines.len()),
+            done,
+        )
+        .unwrap();
+        assert_eq!(distances(&results), distances(&expected));
+        // the rewritten checkpoint holds all pairs
+        assert_eq!(
+            distances(&read(&path, fp, &lines).unwrap()),
+            distances(&expected)
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn checkpoint_of_other_input_is_rejected() {
+        let path =
+            std::env::temp_dir().join(format!("dldist-checkpoint-other-{}", std::process::id()));
+        let lines = to_lines(&["kitten", "sitting"]);
+        let fp = fingerprint(&lines, Algorithm::Osa);
+        fs::write(&path, format!("{} {:016x}\ndone\n", HEADER_PREFIX, fp)).unwrap();
+        let other_lines = to_lines(&["kitten", "mitten"]);
+        let read = read(
+            &path,
+            fingerprint(&other_lines, Algorithm::Osa),
+            &other_lines,
+        );
+        assert!(read.is_err_and(|error| error.kind() == io::ErrorKind::InvalidData));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn fingerprint_depends_on_the_line_boundaries_and_settings() {
+        let lines = to_lines(&["ab", "c"]);
+        let fp = fingerprint(&lines, Algorithm::Osa);
+        assert_eq!(fp, fingerprint(&to_lines(&["ab", "c"]), Algorithm::Osa));
+        assert_ne!(fp, fingerprint(&to_lines(&["a", "bc"]), Algorithm::Osa));
+        assert_ne!(fp, fingerprint(&lines, Algorithm::TokenSet));
+        assert_ne!(
+            fp,
+            input_fingerprint(&lines, 1, &[], Algorithm::Osa, None, None)
+        );
+        assert_ne!(
+            fp,
+            input_fingerprint(
+                &lines,
+                2,
+                &[],
+                Algorithm::Osa,
+                Some(TypoModel::Qwerty),
+                None
+            )
+        );
+        assert_ne!(
+            fp,
+            input_fingerprint(&lines, 2, &[], Algorithm::Osa, None, Some(1))
+        );
+    }
+}
@@ -0,0 +1,292 @@
+//! Defaults for the command line options read from a `dldist.toml` config file and `DLDIST_*` environment variables.
+//!
+//! Every option can be set by its long name, e.g. `thread-num = 4` in the config file or `DLDIST_THREAD_NUM=4` in the
+//! environment. Options given on the command line take precedence over environment variables, which in turn take
+//! precedence over the config file, and defaults conflicting with an option given on the command line are left out.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command, CommandFactory, FromArgMatches};
+
+use crate::modes::{self, Mode};
+
+pub const CONFIG_FILE_NAME: &str = "dldist.toml";
+const ENV_PREFIX: &str = "DLDIST_";
+/// Argument which holds an explicitly given config file.
+const CONFIG_ARG_ID: &str = "config";
+
+/// Returns the name of the environment variable setting the option with the given long name.
+fn env_var_name(long: &str) -> String {
+    format!("{}{}", ENV_PREFIX, long.to_uppercase().replace('-', "_"))
+}
+
+/// Looks for a config file in the current directory and its ancestors.
+fn discover_config_file() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+fn read_config_file(path: &Path) -> Result<toml::Table, String> {
+    let content = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    content
+        .parse::<toml::Table>()
+        .map_err(|error| error.to_string())
+}
+
+fn toml_scalar(key: &str, value: &toml::Value) -> Result<String, String> {
+    match value {
+        toml::Value::String(s) => Ok(s.clone()),
+        toml::Value::Integer(i) => Ok(i.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        _ => Err(format!("unsupported value for '{}'", key)),
+    }
+}
+
+/// Converts a config file value into the raw values of an option. Arrays provide multiple values.
+fn toml_values(key: &str, value: &toml::Value) -> Result<Vec<String>, String> {
+    match value {
+        toml::Value::Array(values) => values.iter().map(|v| toml_scalar(key, v)).collect(),
+        v => toml_scalar(key, v).map(|s| vec![s]),
+    }
+}
+
+/// Converts the raw values of an option into command line arguments.
+fn to_cli_args(long: &str, action: &ArgAction, values: &[String]) -> Result<Vec<OsString>, String> {
+    let flag = OsString::from(format!("--{}", long));
+    let mut cli_args = Vec::new();
+    match action {
+        ArgAction::SetTrue => {
+            for value in values {
+                match value.to_lowercase().as_str() {
+                    "true" | "1" | "yes" => cli_args.push(flag.clone()),
+                    "false" | "0" | "no" | "" => {}
+                    _ => {
+                        return Err(format!(
+                            "expected a boolean for '{}', got '{}'",
+                            long, value
+                        ))
+                    }
+                }
+            }
+        }
+        ArgAction::Count => {
+            for value in values {
+                let cnt = value
+                    .parse::<usize>()
+                    .map_err(|_| format!("expected a count for '{}', got '{}'", long, value))?;
+                cli_args.extend(std::iter::repeat_n(flag.clone(), cnt));
+            }
+        }
+        _ => {
+            for value in values {
+                cli_args.push(flag.clone());
+                cli_args.push(OsString::from(value));
+            }
+        }
+    }
+    Ok(cli_args)
+}
+
+/// Parses the command line arguments after filling in all options not given on the command line from the
+/// environment and the config file. Also returns the mode selected by the subcommand, with the bare invocation
+/// ranking the pairs.
+pub fn parse_arguments<T: CommandFactory + FromArgMatches>() -> (T, Mode) {
+    parse_arguments_from(env::args_os().collect(), |name| env::var(name).ok())
+}
+
+/// Parses the arguments like [`parse_arguments`], with the environment variables looked up by `env_var`.
+fn parse_arguments_from<T: CommandFactory + FromArgMatches>(
+    mut cli_args: Vec<OsString>,
+    env_var: impl Fn(&str) -> Option<String>,
+) -> (T, Mode) {
+    let mut command = modes::with_modes(T::command());
+    // a first lenient pass tells which options have been given on the command line
+    let first_matches = match command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&cli_args)
+    {
+        Ok(m) => m,
+        Err(error) => error.exit(),
+    };
+
+    // the options of a mode are those of the main command, other subcommands do not take them
+    let (mode, given_matches) = match first_matches.subcommand() {
+        Some((name, sub_matches)) => match Mode::from_name(name) {
+            Some(mode) => (mode, sub_matches),
+            None => {
+                return match T::from_arg_matches(&command.get_matches_from(cli_args)) {
+                    Ok(args) => (args, Mode::Pairs),
+                    Err(error) => error.exit(),
+                };
+            }
+        },
+        None => (Mode::Pairs, &first_matches),
+    };
+
+    let config_path = given_matches
+        .try_get_one::<PathBuf>(CONFIG_ARG_ID)
+        .ok()
+        .flatten()
+        .cloned()
+        .or_else(|| env_var(&env_var_name(CONFIG_ARG_ID)).map(PathBuf::from))
+        .or_else(discover_config_file);
+    let mut config = match &config_path {
+        Some(path) => match read_config_file(path) {
+            Ok(table) => table,
+            Err(error) => panic!("Failed to read config file '{}': {}", path.display(), error),
+        },
+        None => toml::Table::new(),
+    };
+
+    let root_command = T::command();
+    for arg in root_command.get_arguments() {
+        let (Some(long), id) = (arg.get_long(), arg.get_id().as_str()) else {
+            continue;
+        };
+        let config_value = config
+            .remove(long)
+            .or_else(|| config.remove(&long.replace('-', "_")));
+        // defaults of options not applying to the mode are left out instead of failing it
+        if id == CONFIG_ARG_ID
+            || !mode.applies(id)
+            || given_on_command_line(given_matches, id)
+            || conflicts_with_given(&root_command, arg, given_matches)
+        {
+            continue;
+        }
+        let values = if let Some(env_value) = env_var(&env_var_name(long)) {
+            vec![env_value]
+        } else if let Some(value) = config_value {
+            match toml_values(long, &value) {
+                Ok(vals) => vals,
+                Err(error) => panic!("Invalid config file: {}", error),
+            }
+        } else {
+            continue;
+        };
+        match to_cli_args(long, arg.get_action(), &values) {
+            Ok(args) => cli_args.extend(args),
+            Err(error) => panic!("Invalid default for '{}': {}", long, error),
+        }
+    }
+    if let Some(key) = config.keys().next() {
+        panic!(
+            "Unknown option '{}' in config file '{}'!",
+            key,
+            config_path.unwrap().display()
+        );
+    }
+
+    let matches = command.clone().get_matches_from(cli_args);
+    let matches = match matches.subcommand() {
+        Some((_, sub_matches)) => {
+            modes::reject_inapplicable_args(&mut command, mode, sub_matches);
+            sub_matches
+        }
+        None => &matches,
+    };
+    match T::from_arg_matches(matches) {
+        Ok(args) => (args, mode),
+        Err(error) => error.exit(),
+    }
+}
+
+fn given_on_command_line(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches
+            .try_contains_id(id)
+            .map(|_| matches.value_source(id)),
+        Ok(Some(ValueSource::CommandLine))
+    )
+}
+
+/// Returns whether the option conflicts with one given on the command line, whichever side declares the conflict.
+fn conflicts_with_given(command: &Command, arg: &Arg, matches: &ArgMatches) -> bool {
+    let is_given = |other: &Arg| given_on_command_line(matches, other.get_id().as_str());
+    command
+        .get_arg_conflicts_with(arg)
+        .into_iter()
+        .any(is_given)
+        || command
+            .get_arguments()
+            .filter(|other| is_given(other))
+            .any(|other| {
+                command
+                    .get_arg_conflicts_with(other)
+                    .iter()
+                    .any(|conflict| conflict.get_id() == arg.get_id())
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZero;
+
+    use crate::Arguments;
+
+    /// Parses the command line arguments with the given config file content and environment variables.
+    fn parse(cli_args: &[&str], config: &str, env_vars: &[(&str, &str)]) -> (Arguments, Mode) {
+        let path = env::temp_dir().join(format!(
+            "dldist-config-{}-{:016x}.toml",
+            std::process::id(),
+            crate::ignore::content_hash(format!("{cli_args:?}{config}{env_vars:?}").as_bytes())
+        ));
+        fs::write(&path, config).unwrap();
+        let mut args: Vec<OsString> = vec![OsString::from("dldist-file")];
+        args.extend(cli_args.iter().map(OsString::from));
+        args.extend([OsString::from("--config"), path.clone().into_os_string()]);
+        let parsed = parse_arguments_from(args, |name| {
+            env_vars
+                .iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        });
+        fs::remove_file(&path).unwrap();
+        parsed
+    }
+
+    #[test]
+    fn command_line_takes_precedence_over_environment_and_config_file() {
+        let config = "thread-num = 3\nwindow = 5\nprint_lines = true\n";
+        let (args, mode) = parse(&["in.txt"], config, &[]);
+        assert!(mode == Mode::Pairs);
+        assert_eq!(args.thread_num, 3);
+        assert_eq!(args.window.map(NonZero::get), Some(5));
+        assert!(args.print_lines);
+
+        let env_vars = [("DLDIST_THREAD_NUM", "4"), ("DLDIST_PRINT_LINES", "false")];
+        let (args, _) = parse(&["in.txt"], config, &env_vars);
+        assert_eq!(args.thread_num, 4);
+        assert!(!args.print_lines);
+
+        let (args, _) = parse(&["in.txt", "-j", "2", "--window", "7"], config, &env_vars);
+        assert_eq!(args.thread_num, 2);
+        assert_eq!(args.window.map(NonZero::get), Some(7));
+    }
+
+    #[test]
+    fn defaults_conflicting_with_the_command_line_are_left_out() {
+        let config = "low-memory = true\nverbose = 2\n";
+        let (args, _) = parse(&["in.txt", "--checkpoint", "run.ckpt"], config, &[]);
+        assert!(!args.low_memory);
+        assert_eq!(args.verbose, 2);
+    }
+
+    #[test]
+    fn defaults_not_applying_to_the_mode_are_left_out() {
+        let config = "best-match = true\nthread-num = 3\n";
+        let (args, mode) = parse(&["dedupe", "in.txt"], config, &[]);
+        assert!(mode == Mode::Dedupe);
+        assert!(!args.best_match);
+        assert_eq!(args.thread_num, 3);
+    }
+}
@@ -0,0 +1,117 @@
+//! Character-wise alignment of the two lines of a pair for highlighting their differences.
+
+/// Upper limit of the size of the alignment matrix, longer lines are shown as replaced as a whole.
+const MAX_CELLS: usize = 1 << 22;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    /// Part of both lines.
+    Equal,
+    /// Only part of the first line.
+    Removed,
+    /// Only part of the second line.
+    Added,
+}
+
+/// Run of characters of the same kind.
+pub struct Segment {
+    pub kind: SegmentKind,
+    pub text: String,
+}
+
+/// Appends the text to the last segment if it has the same kind.
+fn push_segment(segments: &mut Vec<Segment>, kind: SegmentKind, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match segments.last_mut() {
+        Some(last) if last.kind == kind => last.text.push_str(text),
+        _ => segments.push(Segment {
+            kind,
+            text: text.to_string(),
+        }),
+    }
+}
+
+/// Appends the pending run of differing characters, removed ones first.
+fn flush_changes(segments: &mut Vec<Segment>, removed: &mut String, added: &mut String) {
+    push_segment(segments, SegmentKind::Removed, removed);
+    push_segment(segments, SegmentKind::Added, added);
+    removed.clear();
+    added.clear();
+}
+
+/// Returns the segments turning the first line into the second one with the fewest edits. Within a run of differing
+/// characters, the removed ones precede the added ones.
+pub fn diff_lines(line_a: &[u8], line_b: &[u8]) -> Vec<Segment> {
+    let chars_a: Vec<char> = String::from_utf8_lossy(line_a).chars().collect();
+    let chars_b: Vec<char> = String::from_utf8_lossy(line_b).chars().collect();
+    let prefix_len = chars_a
+        .iter()
+        .zip(&chars_b)
+        .take_while(|(x, y)| x == y)
+        .count();
+    let suffix_len = chars_a[prefix_len..]
+        .iter()
+        .rev()
+        .zip(chars_b[prefix_len..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let a = &chars_a[prefix_len..chars_a.len() - suffix_len];
+    let b = &chars_b[prefix_len..chars_b.len() - suffix_len];
+    let prefix: String = chars_a[..prefix_len].iter().collect();
+    let suffix: String = chars_a[chars_a.len() - suffix_len..].iter().collect();
+
+    let mut segments = Vec::new();
+    push_segment(&mut segments, SegmentKind::Equal, &prefix);
+    let (mut removed, mut added) = (String::new(), String::new());
+    if (a.len() + 1) * (b.len() + 1) > MAX_CELLS {
+        removed.extend(a);
+        added.extend(b);
+    } else {
+        // Levenshtein matrix of the suffixes, so that the alignment can be read off from the front
+        let width = b.len() + 1;
+        let mut dist = vec![0u32; (a.len() + 1) * width];
+        for i in (0..=a.len()).rev() {
+            for j in (0..=b.len()).rev() {
+                dist[i * width + j] = if i == a.len() {
+                    (b.len() - j) as u32
+                } else if j == b.len() {
+                    (a.len() - i) as u32
+                } else if a[i] == b[j] {
+                    dist[(i + 1) * width + j + 1]
+                } else {
+                    1 + dist[(i + 1) * width + j + 1]
+                        .min(dist[(i + 1) * width + j])
+                        .min(dist[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            let here = dist[i * width + j];
+            if i < a.len() && j < b.len() && a[i] == b[j] && here == dist[(i + 1) * width + j + 1] {
+                flush_changes(&mut segments, &mut removed, &mut added);
+                push_segment(
+                    &mut segments,
+                    SegmentKind::Equal,
+                    a[i].encode_utf8(&mut [0; 4]),
+                );
+                (i, j) = (i + 1, j + 1);
+            } else if i < a.len() && j < b.len() && here == 1 + dist[(i + 1) * width + j + 1] {
+                removed.push(a[i]);
+                added.push(b[j]);
+                (i, j) = (i + 1, j + 1);
+            } else if i < a.len() && (j == b.len() || here == 1 + dist[(i + 1) * width + j]) {
+                removed.push(a[i]);
+                i += 1;
+            } else {
+                added.push(b[j]);
+                j += 1;
+            }
+        }
+    }
+    flush_changes(&mut segments, &mut removed, &mut added);
+    push_segment(&mut segments, SegmentKind::Equal, &suffix);
+    segments
+}
@@ -0,0 +1,493 @@
+//! Multi-threaded calculation of the distances of many pairs of lines.
+
+use std::cmp::Ordering;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::mpsc::channel;
+use std::sync::Mutex;
+
+use clap::ValueEnum;
+use num_traits::PrimInt;
+#[cfg(not(target_arch = "wasm32"))]
+use threadpool::ThreadPool;
+use tracing::debug;
+
+use crate::osa::{calculate_distance_with, DistanceSettings};
+
+pub static THREAD_NUM: Mutex<usize> = Mutex::new(1);
+pub static NORMALIZATION: Mutex<Normalization> = Mutex::new(Normalization::Mean);
+/// Whether the normalized distances are turned into similarity percentages, see [`similarity_percent`].
+pub static AS_SIMILARITY: Mutex<bool> = Mutex::new(false);
+pub static TUNING: Mutex<Tuning> = Mutex::new(Tuning {
+    chunk_pairs: 1,
+    max_queued_jobs: None,
+});
+
+/// Amount of DP cells a single job should calculate, which is about a millisecond of work and thus keeps the
+/// overhead of handing over jobs and results negligible.
+const JOB_CELLS: usize = 1 << 20;
+const MAX_CHUNK_PAIRS: usize = 4096;
+/// Amount of DP cells below which an additional thread does not pay off.
+const THREAD_CELLS: u64 = 1 << 24;
+
+/// How the pairs are handed over to the threads.
+#[derive(Clone, Copy)]
+pub struct Tuning {
+    /// Amount of pairs calculated by a single job.
+    pub chunk_pairs: usize,
+    /// Amount of jobs waiting for a free thread, which bounds the memory of the pairs in flight. Defaults to the
+    /// thread count.
+    pub max_queued_jobs: Option<usize>,
+}
+
+impl Tuning {
+    /// Derives the tuning from the mean line length, so that short lines are calculated in larger chunks. With a
+    /// memory budget in bytes, the queue is shortened until the pairs in flight fit into it.
+    pub fn for_lines(lines: &[Vec<u8>], thread_num: usize, max_memory: Option<usize>) -> Self {
+        let mean_len = mean_line_len(lines).max(1);
+        let chunk_pairs = (JOB_CELLS / (mean_len * mean_len)).clamp(1, MAX_CHUNK_PAIRS);
+        // every queued pair holds a copy of both lines until its result has been handed over
+        let job_bytes = chunk_pairs
+            * (2 * (mean_len + std::mem::size_of::<Vec<u8>>())
+                + std::mem::size_of::<DistanceResult>());
+        let max_queued_jobs = match max_memory {
+            Some(budget) => (budget / job_bytes).saturating_sub(thread_num).max(1),
+            None => 2 * thread_num,
+        };
+        Tuning {
+            chunk_pairs,
+            max_queued_jobs: Some(max_queued_jobs),
+        }
+    }
+}
+
+fn mean_line_len(lines: &[Vec<u8>]) -> usize {
+    lines.iter().map(Vec::len).sum::<usize>() / lines.len().max(1)
+}
+
+/// Returns the amount of threads worth using for `pairs_cnt` pairs of the lines, which is less than `max_threads` if
+/// there is too little work to spread.
+pub fn auto_thread_num(lines: &[Vec<u8>], pairs_cnt: u64, max_threads: usize) -> usize {
+    let mean_len = mean_line_len(lines).max(1) as u64;
+    let cells = pairs_cnt.saturating_mul(mean_len * mean_len);
+    (cells / THREAD_CELLS).clamp(1, max_threads.max(1) as u64) as usize
+}
+
+/// Settings of a calculation, read once for all pairs instead of locking the globals for every pair. Embedders
+/// running calculations concurrently pass their own settings instead of changing the globals.
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub thread_num: usize,
+    pub normalization: Normalization,
+    pub as_similarity: bool,
+    pub distance: DistanceSettings,
+}
+
+impl Settings {
+    /// Returns the settings as currently set by `THREAD_NUM`, `NORMALIZATION`, `AS_SIMILARITY` and the distance settings.
+    pub fn current() -> Self {
+        Settings {
+            thread_num: *THREAD_NUM.lock().unwrap(),
+            normalization: *NORMALIZATION.lock().unwrap(),
+            as_similarity: *AS_SIMILARITY.lock().unwrap(),
+            distance: DistanceSettings::current(),
+        }
+    }
+
+    /// Returns whether the normalized distances are similarities, which grow for equal-like lines.
+    pub fn is_similarity(&self) -> bool {
+        self.normalization == Normalization::Similarity || self.as_similarity
+    }
+}
+
+/// Distance of a pair of lines, which are referred to by their indices.
+pub struct DistanceResult {
+    pub line_a: u64,
+    pub line_b: u64,
+    pub _mean_line_len: f32,
+    pub dldist: u32,
+    pub normalized_dldist: f32,
+}
+
+impl DistanceResult {
+    /// Returns whether the (normalized) distance of the pair is within the threshold. Similarities have to be at
+    /// least the threshold instead.
+    pub fn is_similar(&self, normalize: bool, threshold: f32, settings: &Settings) -> bool {
+        if !normalize {
+            self.dldist as f32 <= threshold
+        } else if settings.is_similarity() {
+            self.normalized_dldist >= threshold
+        } else {
+            self.normalized_dldist <= threshold
+        }
+    }
+
+    pub fn new(
+        line_a: u64,
+        line_b: u64,
+        line_a_len: usize,
+        line_b_len: usize,
+        dldist: u32,
+        settings: &Settings,
+    ) -> Self {
+        let mean_line_length = ((line_a_len as f32) + (line_b_len as f32)) * 0.5f32;
+        DistanceResult {
+            line_a,
+            line_b,
+            _mean_line_len: mean_line_length,
+            dldist,
+            normalized_dldist: {
+                let normalized = settings.normalization.apply(
+                    dldist as f32 / settings.distance.edit_cost_unit() as f32,
+                    line_a_len,
+                    line_b_len,
+                );
+                if settings.as_similarity {
+                    similarity_percent(settings.normalization, normalized)
+                } else {
+                    normalized
+                }
+            },
+        }
+    }
+}
+
+/// Strategy to normalize the distance of a pair by the lengths of its lines.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Normalization {
+    /// Distance divided by the mean length of both lines. Might exceed 1.0.
+    Mean,
+    /// Distance divided by the length of the longer line, within [0, 1].
+    Max,
+    /// Distance divided by the length of the shorter line. Might exceed 1.0, 1.0 if only one of the lines is empty.
+    Min,
+    /// Distance as a ratio of the length of the longer line, within [0, 1]. The same as `max`.
+    LongerRatio,
+    /// Similarity `1 - d/max(len)` within [0, 1], where 1 means equal lines.
+    Similarity,
+}
+
+impl Normalization {
+    /// Normalizes the distance, which is given in edits.
+    pub fn apply(self, edits: f32, line_a_len: usize, line_b_len: usize) -> f32 {
+        let (a_len, b_len) = (line_a_len as f32, line_b_len as f32);
+        let divisor = match self {
+            Normalization::Mean => (a_len + b_len) * 0.5f32,
+            Normalization::Max | Normalization::LongerRatio | Normalization::Similarity => {
+                a_len.max(b_len)
+            }
+            Normalization::Min => a_len.min(b_len),
+        };
+        // two empty lines are equal, an empty line is entirely different from any other
+        let normalized = if edits == 0f32 {
+            0f32
+        } else if divisor == 0f32 {
+            1f32
+        } else {
+            edits / divisor
+        };
+        if self == Normalization::Similarity {
+            1f32 - normalized
+        } else {
+            normalized
+        }
+    }
+}
+
+/// Turns the normalized distance into a similarity from 0 to 100%. Normalizations which might exceed 1.0 are capped
+/// at 0% similarity, the similarity normalization is only scaled.
+pub fn similarity_percent(normalization: Normalization, normalized: f32) -> f32 {
+    let similarity = if normalization == Normalization::Similarity {
+        normalized
+    } else {
+        (1f32 - normalized).max(0f32)
+    };
+    similarity * 100f32
+}
+
+/// Returns whether the normalized distances are similarities, which grow for equal-like lines.
+pub fn is_similarity() -> bool {
+    Settings::current().is_similarity()
+}
+
+/// Returns the amount of pair-combinations
+pub fn pair_combinations_count<T>(num: T) -> T
+where
+    T: PrimInt + std::convert::From<u32>,
+{
+    if num < 2u32.into() {
+        0u32.into()
+    } else {
+        (num * (num - 1u32.into())) / 2u32.into()
+    }
+}
+
+/// Returns the amount of pair-combinations when each element is only paired with the `window` elements following it
+pub fn window_combinations_count(num: u64, window: u64) -> u64 {
+    if window >= num {
+        pair_combinations_count(num)
+    } else {
+        window * (num - window) + pair_combinations_count(window)
+    }
+}
+
+/// Returns all pairs of indices of a list with `num` elements, ignoring self-comparison and duplicates.
+/// Each element is only paired with the `window` elements following it.
+pub fn window_pairs(num: usize, window: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..num).flat_map(move |la| {
+        (la + 1..num.min(la.saturating_add(window).saturating_add(1))).map(move |lb| (la, lb))
+    })
+}
+
+/// Returns only those pairs of [`window_pairs`] which contain one of the given focus indices, which have to be sorted.
+pub fn focus_pairs(
+    num: usize,
+    window: usize,
+    focus_lines: &[usize],
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+    focus_lines.iter().flat_map(move |&f| {
+        (f.saturating_sub(window)..num.min(f.saturating_add(window).saturating_add(1)))
+            // a pair of two focus lines is only returned for the first of them
+            .filter(move |&o| o != f && !(o < f && focus_lines.binary_search(&o).is_ok()))
+            .map(move |o| (f.min(o), f.max(o)))
+    })
+}
+
+/// Returns the pairs to calculate, which are all pairs within the window or only the focused ones if any are given.
+pub fn selected_pairs<'a>(
+    num: usize,
+    window: usize,
+    focus_lines: &'a [usize],
+) -> Box<dyn Iterator<Item = (usize, usize)> + 'a> {
+    if focus_lines.is_empty() {
+        Box::new(window_pairs(num, window))
+    } else {
+        Box::new(focus_pairs(num, window, focus_lines))
+    }
+}
+
+/// Returns all pairs of indices between two lists with `num_a` and `num_b` elements.
+pub fn cross_pairs(num_a: usize, num_b: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..num_a).flat_map(move |la| (0..num_b).map(move |lb| (la, lb)))
+}
+
+/// Calculates the distances of the given index pairs, where the first index of a pair refers to `lines_a` and the
+/// second one to `lines_b`.
+pub fn calculate_osa_distances<I>(
+    lines_a: &[Vec<u8>],
+    lines_b: &[Vec<u8>],
+    pairs: I,
+) -> Vec<DistanceResult>
+where
+    I: Iterator<Item = (usize, usize)>,
+{
+    calculate_osa_distances_with(lines_a, lines_b, pairs, &Settings::current())
+}
+
+/// Calculates the distances of the given index pairs like [`calculate_osa_distances`], but with the given settings
+/// instead of the globals.
+pub fn calculate_osa_distances_with<I>(
+    lines_a: &[Vec<u8>],
+    lines_b: &[Vec<u8>],
+    pairs: I,
+    settings: &Settings,
+) -> Vec<DistanceResult>
+where
+    I: Iterator<Item = (usize, usize)>,
+{
+    let mut results = Vec::new();
+    for_each_osa_distance_with(lines_a, lines_b, pairs, settings, |dr| results.push(dr));
+    results
+}
+
+/// Calculates the distances of the given index pairs like [`calculate_osa_distances`], but hands every result over to
+/// `on_result` on the calling thread as soon as it arrives instead of collecting them.
+pub fn for_each_osa_distance<I, F>(lines_a: &[Vec<u8>], lines_b: &[Vec<u8>], pairs: I, on_result: F)
+where
+    I: Iterator<Item = (usize, usize)>,
+    F: FnMut(DistanceResult),
+{
+    for_each_osa_distance_with(lines_a, lines_b, pairs, &Settings::current(), on_result);
+}
+
+/// Calculates the distances of the given index pairs like [`for_each_osa_distance`], but with the given settings
+/// instead of the globals.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn for_each_osa_distance_with<I, F>(
+    lines_a: &[Vec<u8>],
+    lines_b: &[Vec<u8>],
+    pairs: I,
+    settings: &Settings,
+    on_result: F,
+) where
+    I: Iterator<Item = (usize, usize)>,
+    F: FnMut(DistanceResult),
+{
+    let pool = ThreadPool::new(settings.thread_num);
+    for_each_osa_distance_in(&pool, lines_a, lines_b, pairs, settings, on_result);
+}
+
+/// Calculates the distances of the given index pairs like [`for_each_osa_distance_with`], but on the threads of an
+/// existing pool, whose thread count takes precedence over the one of the settings. The pool may be shared by several
+/// calculations at the same time, their jobs take turns on its threads.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn for_each_osa_distance_in<I, F>(
+    pool: &ThreadPool,
+    lines_a: &[Vec<u8>],
+    lines_b: &[Vec<u8>],
+    pairs: I,
+    settings: &Settings,
+    mut on_result: F,
+) where
+    I: Iterator<Item = (usize, usize)>,
+    F: FnMut(DistanceResult),
+{
+    let tuning = *TUNING.lock().unwrap();
+    let settings = *settings;
+    // jobs of this calculation which are waiting or running, the pool itself might hold jobs of other ones as well
+    let max_pending_jobs =
+        tuning.max_queued_jobs.unwrap_or(pool.max_count()).max(1) + pool.max_count();
+    let mut pending_jobs = 0usize;
+    let mut pairs = pairs.peekable();
+    let (tx, rx) = channel::<Vec<DistanceResult>>();
+
+    while pairs.peek().is_some() {
+        let chunk: Vec<(usize, usize, Vec<u8>, Vec<u8>)> = pairs
+            .by_ref()
+            .take(tuning.chunk_pairs)
+            .map(|(la, lb)| (la, lb, lines_a[la].clone(), lines_b[lb].clone()))
+            .collect();
+        let tx_child = tx.clone();
+        pool.execute(move || {
+            let results = chunk
+                .into_iter()
+                .map(|(la, lb, line_a, line_b)| {
+                    let distance = calculate_distance_with(&line_a, &line_b, &settings.distance);
+                    debug!(line_a = la, line_b = lb, distance, "calculated pair");
+                    DistanceResult::new(
+                        la as u64,
+                        lb as u64,
+                        line_a.len(),
+                        line_b.len(),
+                        distance,
+                        &settings,
+                    )
+                })
+                .collect();
+            tx_child.send(results).unwrap();
+        });
+        pending_jobs += 1;
+
+        // This prevents from spamming the queue and thus the memory.
+        // That way it makes sure at most the tuned amount of jobs is waiting for a free thread.
+        while pending_jobs >= max_pending_jobs {
+            rx.recv().unwrap().into_iter().for_each(&mut on_result);
+            pending_jobs -= 1;
+        }
+
+        // hand over what has been finished so far, so that results do not pile up in the channel
+        for results in rx.try_iter() {
+            results.into_iter().for_each(&mut on_result);
+            pending_jobs -= 1;
+        }
+    }
+
+    // the channel is closed as soon as the remaining jobs have dropped their senders
+    drop(tx);
+    rx.iter().flatten().for_each(on_result);
+}
+
+/// Calculates the distances of the given index pairs one after another on the calling thread, as there are no threads
+/// to spread them over on wasm32. The thread count of the settings is ignored.
+#[cfg(target_arch = "wasm32")]
+pub fn for_each_osa_distance_with<I, F>(
+    lines_a: &[Vec<u8>],
+    lines_b: &[Vec<u8>],
+    pairs: I,
+    settings: &Settings,
+    mut on_result: F,
+) where
+    I: Iterator<Item = (usize, usize)>,
+    F: FnMut(DistanceResult),
+{
+    for (la, lb) in pairs {
+        let (line_a, line_b) = (&lines_a[la], &lines_b[lb]);
+        let distance = calculate_distance_with(line_a, line_b, &settings.distance);
+        debug!(line_a = la, line_b = lb, distance, "calculated pair");
+        on_result(DistanceResult::new(
+            la as u64,
+            lb as u64,
+            line_a.len(),
+            line_b.len(),
+            distance,
+            settings,
+        ));
+    }
+}
+
+/// Returns the comparison of results by their (normalized) distance in the selected order. Pairs with equal
+/// distances are always ordered ascending by their line numbers, so that the order does not depend on the order in
+/// which the threads finished.
+pub fn distance_result_order(
+    normalize: bool,
+    descending: bool,
+    settings: &Settings,
+) -> impl Fn(&DistanceResult, &DistanceResult) -> Ordering + Copy {
+    // similarities grow for equal-like lines, thus they have to be sorted the other way round
+    let reverse = descending != (normalize && settings.is_similarity());
+    move |a: &DistanceResult, b: &DistanceResult| {
+        let ordering = if normalize {
+            a.normalized_dldist
+                .partial_cmp(&b.normalized_dldist)
+                .unwrap_or(Ordering::Equal)
+        } else {
+            a.dldist.cmp(&b.dldist)
+        };
+        let ordering = if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering.then_with(|| (a.line_a, a.line_b).cmp(&(b.line_a, b.line_b)))
+    }
+}
+
+/// Sorts the results by their (normalized) distance, see [`distance_result_order`].
+pub fn sort_distance_results(
+    distance_results: &mut [DistanceResult],
+    normalize: bool,
+    descending: bool,
+    settings: &Settings,
+) {
+    distance_results.sort_by(distance_result_order(normalize, descending, settings));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_pairs_with_huge_window_pairs_all() {
+        let pairs: Vec<_> = window_pairs(4, usize::MAX).collect();
+        assert_eq!(pairs, window_pairs(4, 4).collect::<Vec<_>>());
+        assert_eq!(pairs.len() as u64, pair_combinations_count(4u64));
+    }
+
+    #[test]
+    fn focus_pairs_with_huge_window_pairs_all_partners() {
+        let pairs: Vec<_> = focus_pairs(4, usize::MAX, &[1]).collect();
+        assert_eq!(pairs, vec![(0, 1), (1, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn normalizations_of_empty_lines_are_finite() {
+        assert_eq!(Normalization::Min.apply(0f32, 0, 0), 0f32);
+        assert_eq!(Normalization::Min.apply(3f32, 0, 3), 1f32);
+        assert_eq!(Normalization::Mean.apply(0f32, 0, 0), 0f32);
+        assert_eq!(Normalization::Similarity.apply(0f32, 0, 0), 1f32);
+    }
+
+    #[test]
+    fn longer_ratio_divides_by_the_longer_line() {
+        assert_eq!(Normalization::LongerRatio.apply(2f32, 4, 8), 0.25f32);
+    }
+}
@@ -0,0 +1,553 @@
+//! Out-of-core mode for inputs which do not fit into memory.
+//!
+//! Only an index of the positions of the lines is kept in memory. The lines are read in block by block and all pairs
+//! between two blocks are calculated at once. The results are sorted in chunks and spilled into run files, which are
+//! merged for printing the final ranking.
+
+use std::cmp::Ordering;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use dldist::engine::{
+    calculate_osa_distances, distance_result_order, window_combinations_count, window_pairs,
+    DistanceResult, Settings,
+};
+
+use crate::{Arguments, LineSpan, LongLines};
+
+/// Amount of lines read in at once.
+const BLOCK_LINES: usize = 2048;
+/// Amount of results collected in memory before they are sorted and spilled into a run file.
+const RUN_RESULTS: usize = 1 << 22;
+/// Maximum amount of run files merged at once, larger amounts are merged in multiple passes.
+const MERGE_FAN_IN: usize = 64;
+/// Size of a result within a run file.
+const RESULT_BYTES: usize = 28;
+
+/// Position of a line within the input files.
+struct LineEntry {
+    file: usize,
+    /// 1-based line number within the file.
+    number: usize,
+    offset: u64,
+    len: usize,
+}
+
+struct LineIndex {
+    files: Vec<PathBuf>,
+    entries: Vec<LineEntry>,
+    label_with_path: bool,
+}
+
+impl LineIndex {
+    fn build(files: &[PathBuf]) -> io::Result<Self> {
+        let mut entries = Vec::new();
+        let mut raw = Vec::new();
+        for (file, path) in files.iter().enumerate() {
+            let mut reader = BufReader::new(File::open(path)?);
+            let (mut offset, mut number) = (0u64, 0usize);
+            loop {
+                raw.clear();
+                let read_cnt = reader.read_until(b'\n', &mut raw)?;
+                if read_cnt == 0 {
+                    break;
+                }
+                number += 1;
+                // same line endings as in [`crate::split_lines`]
+                let mut len = raw.len();
+                if raw[len - 1] == b'\n' {
+                    len -= 1;
+                }
+                if len > 0 && raw[len - 1] == b'\r' {
+                    len -= 1;
+                }
+                entries.push(LineEntry {
+                    file,
+                    number,
+                    offset,
+                    len,
+                });
+                offset += read_cnt as u64;
+            }
+        }
+        Ok(LineIndex {
+            files: files.to_vec(),
+            entries,
+            label_with_path: files.len() > 1,
+        })
+    }
+
+    fn label(&self, i: usize) -> String {
+        let entry = &self.entries[i];
+        if self.label_with_path {
+            format!("{}:{}", self.files[entry.file].display(), entry.number)
+        } else {
+            format!("{}", entry.number)
+        }
+    }
+
+    fn span(&self, i: usize) -> LineSpan {
+        let entry = &self.entries[i];
+        LineSpan {
+            offset: entry.offset,
+            len: entry.len,
+        }
+    }
+
+    /// Reads the raw lines of the given range of entries.
+    fn read(&self, range: Range<usize>) -> io::Result<Vec<Vec<u8>>> {
+        let mut lines = Vec::with_capacity(range.len());
+        let mut current: Option<(usize, BufReader<File>, u64)> = None;
+        for entry in &self.entries[range] {
+            if current
+                .as_ref()
+                .is_none_or(|(file, _, _)| *file != entry.file)
+            {
+                let reader = BufReader::new(File::open(&self.files[entry.file])?);
+                current = Some((entry.file, reader, 0));
+            }
+            let (_, reader, pos) = current.as_mut().unwrap();
+            // the entries of a block are mostly adjacent, so that relative seeks can keep the buffer
+            reader.seek_relative(entry.offset as i64 - *pos as i64)?;
+            let mut line = vec![0u8; entry.len];
+            reader.read_exact(&mut line)?;
+            *pos = entry.offset + entry.len as u64;
+            lines.push(line);
+        }
+        Ok(lines)
+    }
+
+    /// Reads the lines of the given range of entries and prepares them for comparing.
+    fn read_block(&self, args: &Arguments, range: Range<usize>) -> Vec<Vec<u8>> {
+        let start = range.start;
+        let raw_lines = match self.read(range) {
+            Ok(lns) => lns,
+            Err(error) => panic!("Failed to read in lines from file: {error:?}"),
+        };
+        let lines: Vec<Vec<u8>> = raw_lines
+            .into_iter()
+            .enumerate()
+            .map(
+                |(i, raw)| match crate::decode_text(raw, args.invalid_utf8) {
+                    Ok(text) => text,
+                    Err(error) => panic!(
+                        "Failed to read in line {}: not valid UTF-8 ({error}), see --invalid-utf8",
+                        self.label(start + i)
+                    ),
+                },
+            )
+            .collect();
+        crate::prepare_lines(&lines, args).into_owned()
+    }
+}
+
+fn write_result<W: Write>(writer: &mut W, dr: &DistanceResult) -> io::Result<()> {
+    writer.write_all(&dr.line_a.to_le_bytes())?;
+    writer.write_all(&dr.line_b.to_le_bytes())?;
+    writer.write_all(&dr._mean_line_len.to_le_bytes())?;
+    writer.write_all(&dr.dldist.to_le_bytes())?;
+    writer.write_all(&dr.normalized_dldist.to_le_bytes())
+}
+
+fn read_result<R: Read>(reader: &mut R) -> io::Result<Option<DistanceResult>> {
+    let mut buf = [0u8; RESULT_BYTES];
+    match reader.read_exact(&mut buf) {
+        Ok(()) => {}
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    }
+    let u64_at = |i: usize| u64::from_le_bytes(buf[i..i + 8].try_into().unwrap());
+    let u32_at = |i: usize| u32::from_le_bytes(buf[i..i + 4].try_into().unwrap());
+    Ok(Some(DistanceResult {
+        line_a: u64_at(0),
+        line_b: u64_at(8),
+        _mean_line_len: f32::from_bits(u32_at(16)),
+        dldist: u32_at(20),
+        normalized_dldist: f32::from_bits(u32_at(24)),
+    }))
+}
+
+/// Sorted results spilled into files.
+struct Runs<F> {
+    dir: PathBuf,
+    paths: Vec<PathBuf>,
+    created_cnt: usize,
+    order: F,
+    /// Amount of results which are printed at most, so that every run only has to keep its best ones.
+    limit: Option<usize>,
+}
+
+impl<F> Runs<F>
+where
+    F: Fn(&DistanceResult, &DistanceResult) -> Ordering + Copy,
+{
+    fn next_path(&mut self) -> PathBuf {
+        self.created_cnt += 1;
+        self.dir.join(format!("run-{}.bin", self.created_cnt))
+    }
+
+    fn spill(&mut self, results: &mut Vec<DistanceResult>) -> io::Result<()> {
+        results.sort_by(self.order);
+        if let Some(limit) = self.limit {
+            results.truncate(limit);
+        }
+        let path = self.next_path();
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for dr in results.iter() {
+            write_result(&mut writer, dr)?;
+        }
+        writer.flush()?;
+        self.paths.push(path);
+        results.clear();
+        Ok(())
+    }
+
+    /// Merges the given runs and hands their results over in order, up to the limit.
+    fn merge<C>(&self, paths: &[PathBuf], mut on_result: C) -> io::Result<()>
+    where
+        C: FnMut(DistanceResult) -> io::Result<()>,
+    {
+        let mut readers = paths
+            .iter()
+            .map(|path| File::open(path).map(BufReader::new))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut heads = readers
+            .iter_mut()
+            .map(read_result)
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut merged_cnt = 0usize;
+        while self.limit.is_none_or(|limit| merged_cnt < limit) {
+            // the fan-in is small enough for a linear search of the next result
+            let next = heads
+                .iter()
+                .enumerate()
+                .filter_map(|(i, head)| head.as_ref().map(|dr| (i, dr)))
+                .min_by(|(_, a), (_, b)| (self.order)(a, b))
+                .map(|(i, _)| i);
+            let Some(i) = next else {
+                break;
+            };
+            let dr = std::mem::replace(&mut heads[i], read_result(&mut readers[i])?).unwrap();
+            on_result(dr)?;
+            merged_cnt += 1;
+        }
+        Ok(())
+    }
+
+    /// Merges runs until only few enough are left for a single final merge.
+    fn reduce(&mut self) -> io::Result<()> {
+        while self.paths.len() > MERGE_FAN_IN {
+            let paths: Vec<PathBuf> = self.paths.drain(..MERGE_FAN_IN).collect();
+            let path = self.next_path();
+            let mut writer = BufWriter::new(File::create(&path)?);
+            self.merge(&paths, |dr| write_result(&mut writer, &dr))?;
+            writer.flush()?;
+            for merged_path in &paths {
+                fs::remove_file(merged_path)?;
+            }
+            self.paths.push(path);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the pairs between the two blocks, in indices relative to their starts.
+fn block_pairs(
+    range_a: &Range<usize>,
+    range_b: &Range<usize>,
+    window: usize,
+) -> Box<dyn Iterator<Item = (usize, usize)>> {
+    if range_a == range_b {
+        return Box::new(window_pairs(range_a.len(), window));
+    }
+    let (start_a, start_b, len_b) = (range_a.start, range_b.start, range_b.len());
+    Box::new((0..range_a.len()).flat_map(move |i| {
+        (0..len_b)
+            .take_while(move |j| start_b + j - (start_a + i) <= window)
+            .map(move |j| (i, j))
+    }))
+}
+
+/// Calculates all pairs of lines of the input files without holding them in memory and prints the ranking.
+pub fn run(args: &Arguments, input_files: &[PathBuf]) {
+    for input_file in input_files {
+        println!("==> Indexing '{}'...", input_file.display());
+    }
+    let mut index = match LineIndex::build(input_files) {
+        Ok(idx) => idx,
+        Err(error) => panic!("Failed to index lines of input files: {error:?}"),
+    };
+    if let Some(max_len) = args.max_line_len.map(std::num::NonZero::get) {
+        let long_cnt = index.entries.iter().filter(|e| e.len > max_len).count();
+        match args.long_lines {
+            LongLines::Error => {
+                if let Some(i) = index.entries.iter().position(|e| e.len > max_len) {
+                    println!(
+                        "The line {} is longer than {} bytes ({} bytes), see --long-lines!",
+                        index.label(i),
+                        max_len,
+                        index.entries[i].len
+                    );
+                    return;
+                }
+            }
+            LongLines::Skip => index.entries.retain(|e| e.len <= max_len),
+            // the lines are truncated when preparing them
+            LongLines::Truncate => {}
+        }
+        if long_cnt > 0 {
+            println!(
+                "==> {} lines longer than {} bytes are {}.",
+                long_cnt,
+                max_len,
+                if args.long_lines == LongLines::Skip {
+                    "skipped"
+                } else {
+                    "truncated"
+                }
+            );
+        }
+    }
+    if args.min_len.is_some() || args.max_len.is_some() {
+        let len_range = args.min_len.unwrap_or(0)..=args.max_len.unwrap_or(usize::MAX);
+        let indexed_cnt = index.entries.len();
+        index.entries.retain(|e| len_range.contains(&e.len));
+        if index.entries.len() < indexed_cnt {
+            println!(
+                "==> {} lines {} bytes are skipped.",
+                indexed_cnt - index.entries.len(),
+                crate::len_bounds(args)
+            );
+        }
+    }
+    let lines_cnt = index.entries.len();
+    if lines_cnt < 2 {
+        println!(
+            "The input has to contain at least two lines! Counted {}.",
+            lines_cnt
+        );
+        return;
+    }
+
+    let window = args.window.map_or(lines_cnt, std::num::NonZero::get);
+    let combinations_cnt = window_combinations_count(lines_cnt as u64, window as u64);
+    println!(
+        "==> Calculating {} Damerau-Levenshtein distances between {} lines in blocks of {} lines...",
+        combinations_cnt, lines_cnt, BLOCK_LINES
+    );
+    let dir = env::temp_dir().join(format!("dldist-{}", std::process::id()));
+    if let Err(error) = fs::create_dir_all(&dir) {
+        panic!("Failed to create directory for spilled results: {error:?}");
+    }
+    let mut runs = Runs {
+        dir,
+        paths: Vec::new(),
+        created_cnt: 0,
+        order: distance_result_order(args.normalize, args.descending, &Settings::current()),
+        limit: crate::print_cnt_limit(args, combinations_cnt).map(|cnt| cnt as usize),
+    };
+
+    let start_time = Instant::now();
+    let block_cnt = lines_cnt.div_ceil(BLOCK_LINES);
+    let block_range = |b: usize| b * BLOCK_LINES..lines_cnt.min((b + 1) * BLOCK_LINES);
+    let mut pending = Vec::new();
+    let mut results_cnt = 0u64;
+    for block_a in 0..block_cnt {
+        let range_a = block_range(block_a);
+        let lines_a = index.read_block(args, range_a.clone());
+        for block_b in block_a..block_cnt {
+            let range_b = block_range(block_b);
+            // the following blocks are even further away
+            if block_b > block_a && range_b.start - (range_a.end - 1) > window {
+                break;
+            }
+            let lines_b = if block_b == block_a {
+                None
+            } else {
+                Some(index.read_block(args, range_b.clone()))
+            };
+            let pairs = block_pairs(&range_a, &range_b, window);
+            let results =
+                calculate_osa_distances(&lines_a, lines_b.as_ref().unwrap_or(&lines_a), pairs);
+            for mut dr in results {
+                dr.line_a += range_a.start as u64;
+                dr.line_b += range_b.start as u64;
+                pending.push(dr);
+                results_cnt += 1;
+                if pending.len() >= RUN_RESULTS {
+                    if let Err(error) = runs.spill(&mut pending) {
+                        panic!("Failed to spill results: {error:?}");
+                    }
+                }
+            }
+        }
+        tracing::info!(block = block_a + 1, blocks = block_cnt, "finished block");
+    }
+    if !pending.is_empty() {
+        if let Err(error) = runs.spill(&mut pending) {
+            panic!("Failed to spill results: {error:?}");
+        }
+    }
+    if results_cnt != combinations_cnt {
+        panic!("Somehow the size of the result combinations list ({}) does not equal the theoretical count ({})!?",
+            results_cnt,
+            combinations_cnt);
+    }
+    println!(
+        "Calculations done within {:.4}s, spilled into {} sorted runs in '{}'.",
+        start_time.elapsed().as_secs_f32(),
+        runs.paths.len(),
+        runs.dir.display()
+    );
+    if let Err(error) = runs.reduce() {
+        panic!("Failed to merge spilled results: {error:?}");
+    }
+
+    crate::print_ranking_header(args, results_cnt);
+    // a template shows the lines only by its placeholders, regardless of --print-lines
+    let read_lines = match &args.template {
+        Some(template) => template.contains("{text_a}") || template.contains("{text_b}"),
+        None => args.print_lines,
+    };
+    let merged = runs.merge(&runs.paths, |dr| {
+        let (la, lb) = (dr.line_a as usize, dr.line_b as usize);
+        let (line_a, line_b) = if read_lines {
+            // printing the original lines, not the prepared ones
+            (
+                index.read(la..la + 1)?.pop().unwrap(),
+                index.read(lb..lb + 1)?.pop().unwrap(),
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        crate::print_distance_result(
+            args,
+            &dr,
+            (&index.label(la), &index.label(lb)),
+            (&line_a, &line_b),
+            (index.span(la), index.span(lb)),
+        );
+        Ok(())
+    });
+    if let Err(error) = merged {
+        panic!("Failed to merge spilled results: {error:?}");
+    }
+    if let Err(error) = fs::remove_dir_all(&runs.dir) {
+        println!(
+            "WARN: Could not remove the spilled results in '{}': {error}",
+            runs.dir.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results(cnt: u64) -> Vec<DistanceResult> {
+        let settings = Settings::current();
+        (0..cnt)
+            .map(|i| DistanceResult::new(i, i + 1, 10, 20, ((i * 7919) % 13) as u32, &settings))
+            .collect()
+    }
+
+    fn pairs(results: &[DistanceResult]) -> Vec<(u64, u64, u32)> {
+        results
+            .iter()
+            .map(|dr| (dr.line_a, dr.line_b, dr.dldist))
+            .collect()
+    }
+
+    #[test]
+    fn results_are_read_back_as_written() {
+        let written = results(3);
+        let mut buf = Vec::new();
+        for dr in &written {
+            write_result(&mut buf, dr).unwrap();
+        }
+        assert_eq!(buf.len(), 3 * RESULT_BYTES);
+        let mut reader = &buf[..];
+        let mut read = Vec::new();
+        while let Some(dr) = read_result(&mut reader).unwrap() {
+            read.push(dr);
+        }
+        assert_eq!(pairs(&read), pairs(&written));
+        assert_eq!(read[2].normalized_dldist, written[2].normalized_dldist);
+    }
+
+    fn merged(limit: Option<usize>) -> (Vec<DistanceResult>, Vec<DistanceResult>) {
+        let dir = env::temp_dir().join(format!(
+            "dldist-test-{}-{}",
+            std::process::id(),
+            limit.unwrap_or(0)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let mut runs = Runs {
+            dir: dir.clone(),
+            paths: Vec::new(),
+            created_cnt: 0,
+            order: distance_result_order(false, false, &Settings::current()),
+            limit,
+        };
+        // more runs than merged at once, so that they are reduced in multiple passes
+        let mut spilled = results(1000);
+        while !spilled.is_empty() {
+            let mut chunk: Vec<DistanceResult> = spilled.drain(..spilled.len().min(7)).collect();
+            runs.spill(&mut chunk).unwrap();
+        }
+        assert!(runs.paths.len() > MERGE_FAN_IN);
+        runs.reduce().unwrap();
+        assert!(runs.paths.len() <= MERGE_FAN_IN);
+        let mut merged = Vec::new();
+        runs.merge(&runs.paths, |dr| {
+            merged.push(dr);
+            Ok(())
+        })
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut expected = results(1000);
+        expected.sort_by(runs.order);
+        if let Some(limit) = limit {
+            expected.truncate(limit);
+        }
+        (merged, expected)
+    }
+
+    #[test]
+    fn spilled_runs_merge_into_the_sorted_results() {
+        let (merged, expected) = merged(None);
+        assert_eq!(pairs(&merged), pairs(&expected));
+    }
+
+    #[test]
+    fn spilled_runs_merge_into_the_best_results_up_to_the_limit() {
+        let (merged, expected) = merged(Some(5));
+        assert_eq!(pairs(&merged), pairs(&expected));
+    }
+
+    #[test]
+    fn block_pairs_cover_the_window_pairs() {
+        let (lines_cnt, block_lines, window) = (10, 3, 4);
+        let block_range = |b: usize| b * block_lines..lines_cnt.min((b + 1) * block_lines);
+        let blocks = lines_cnt.div_ceil(block_lines);
+        let mut covered: Vec<(usize, usize)> = Vec::new();
+        for block_a in 0..blocks {
+            for block_b in block_a..blocks {
+                let (range_a, range_b) = (block_range(block_a), block_range(block_b));
+                covered.extend(
+                    block_pairs(&range_a, &range_b, window)
+                        .map(|(i, j)| (range_a.start + i, range_b.start + j)),
+                );
+            }
+        }
+        covered.sort_unstable();
+        let mut expected: Vec<(usize, usize)> = window_pairs(lines_cnt, window).collect();
+        expected.sort_unstable();
+        assert_eq!(covered, expected);
+    }
+}
@@ -0,0 +1,242 @@
+//! Calculation of the distances on the GPU via wgpu compute shaders, batching many pairs per dispatch.
+
+use std::borrow::Cow;
+use std::sync::mpsc::channel;
+
+use wgpu::util::DeviceExt;
+
+use dldist::engine::{DistanceResult, Settings};
+
+const SHADER: &str = include_str!("osa.wgsl");
+const WORKGROUP_SIZE: u32 = 64;
+/// Upper bound for the amount of pairs per dispatch.
+const MAX_BATCH_SIZE: usize = 1 << 20;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    pair_cnt: u32,
+    row_stride: u32,
+}
+
+struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    max_buffer_size: u64,
+}
+
+fn init() -> Result<Gpu, String> {
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::HighPerformance,
+        ..Default::default()
+    }))
+    .map_err(|error| format!("no GPU adapter available ({})", error))?;
+    tracing::info!(adapter = adapter.get_info().name, "using GPU adapter");
+    let limits = adapter.limits();
+    let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        label: Some("dldist"),
+        required_limits: limits.clone(),
+        ..Default::default()
+    }))
+    .map_err(|error| format!("failed to open GPU device ({})", error))?;
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("osa"),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("osa"),
+        layout: None,
+        module: &module,
+        entry_point: Some("main"),
+        compilation_options: Default::default(),
+        cache: None,
+    });
+    Ok(Gpu {
+        device,
+        queue,
+        pipeline,
+        max_buffer_size: (limits.max_storage_buffer_binding_size as u64)
+            .min(limits.max_buffer_size),
+    })
+}
+
+fn storage_buffer<T: bytemuck::Pod>(
+    device: &wgpu::Device,
+    label: &str,
+    contents: &[T],
+) -> wgpu::Buffer {
+    // empty bindings are not allowed
+    let bytes: &[u8] = bytemuck::cast_slice(contents);
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: if bytes.is_empty() { &[0u8; 4] } else { bytes },
+        usage: wgpu::BufferUsages::STORAGE,
+    })
+}
+
+/// Calculates the distances of the given index pairs of `lines` on the GPU. Fails if no GPU is available or the
+/// lines are too long to fit the DP rows of a single pair into a GPU buffer.
+pub fn calculate_osa_distances<I>(
+    lines: &[Vec<u8>],
+    pairs: I,
+) -> Result<Vec<DistanceResult>, String>
+where
+    I: Iterator<Item = (usize, usize)>,
+{
+    let gpu = init()?;
+
+    // all lines are uploaded once, packed with four bytes per u32
+    let mut text = Vec::with_capacity(lines.iter().map(Vec::len).sum::<usize>() / 4 + 1);
+    let mut offsets = Vec::with_capacity(lines.len());
+    let mut lens = Vec::with_capacity(lines.len());
+    let mut packed_len = 0usize;
+    for line in lines {
+        offsets.push(packed_len as u32);
+        lens.push(line.len() as u32);
+        for &byte in line {
+            if packed_len.is_multiple_of(4) {
+                text.push(0u32);
+            }
+            *text.last_mut().unwrap() |= (byte as u32) << ((packed_len % 4) * 8);
+            packed_len += 1;
+        }
+    }
+    if (packed_len as u64) > gpu.max_buffer_size {
+        return Err("input is too large for a single GPU buffer".to_string());
+    }
+    let text_buffer = storage_buffer(&gpu.device, "text", &text);
+    let offsets_buffer = storage_buffer(&gpu.device, "offsets", &offsets);
+    let lens_buffer = storage_buffer(&gpu.device, "lens", &lens);
+
+    // the shorter line of a pair spans the DP rows, so the stride only has to fit the longest of those
+    let row_stride = lines.iter().map(Vec::len).max().unwrap_or(0) as u64 + 1;
+    let batch_size = ((gpu.max_buffer_size / (3 * row_stride * 4)) as usize).min(MAX_BATCH_SIZE);
+    if batch_size == 0 {
+        return Err("lines are too long for the GPU buffers".to_string());
+    }
+    let scratch_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("scratch"),
+        size: batch_size as u64 * 3 * row_stride * 4,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let pairs_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("pairs"),
+        size: batch_size as u64 * 8,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let distances_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("distances"),
+        size: batch_size as u64 * 4,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let readback_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback"),
+        size: batch_size as u64 * 4,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let params_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("params"),
+        size: std::mem::size_of::<Params>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("osa"),
+        layout: &gpu.pipeline.get_bind_group_layout(0),
+        entries: &[
+            &text_buffer,
+            &offsets_buffer,
+            &lens_buffer,
+            &pairs_buffer,
+            &scratch_buffer,
+            &distances_buffer,
+            &params_buffer,
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, buffer)| wgpu::BindGroupEntry {
+            binding: i as u32,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect::<Vec<_>>(),
+    });
+
+    let settings = Settings::current();
+    let mut results = Vec::new();
+    let mut pairs = pairs.peekable();
+    let mut batch_pairs: Vec<(usize, usize)> = Vec::with_capacity(batch_size);
+    let mut batch: Vec<[u32; 2]> = Vec::with_capacity(batch_size);
+    while pairs.peek().is_some() {
+        batch_pairs.clear();
+        batch_pairs.extend(pairs.by_ref().take(batch_size));
+        batch.clear();
+        batch.extend(batch_pairs.iter().map(|&(la, lb)| {
+            // OSA is symmetric, so the shorter line can always be the second one
+            if lines[la].len() >= lines[lb].len() {
+                [la as u32, lb as u32]
+            } else {
+                [lb as u32, la as u32]
+            }
+        }));
+        let params = Params {
+            pair_cnt: batch.len() as u32,
+            row_stride: row_stride as u32,
+        };
+        gpu.queue
+            .write_buffer(&pairs_buffer, 0, bytemuck::cast_slice(&batch));
+        gpu.queue
+            .write_buffer(&params_buffer, 0, bytemuck::bytes_of(&params));
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("osa") });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("osa"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&gpu.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups((batch.len() as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        let result_size = batch.len() as u64 * 4;
+        encoder.copy_buffer_to_buffer(&distances_buffer, 0, &readback_buffer, 0, result_size);
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let (tx, rx) = channel();
+        readback_buffer.map_async(wgpu::MapMode::Read, 0..result_size, move |res| {
+            tx.send(res).unwrap();
+        });
+        gpu.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|error| format!("failed waiting for the GPU ({})", error))?;
+        rx.recv()
+            .unwrap()
+            .map_err(|error| format!("failed reading back GPU results ({})", error))?;
+        {
+            let view = readback_buffer
+                .get_mapped_range(0..result_size)
+                .map_err(|error| format!("failed reading back GPU results ({})", error))?;
+            let distances: &[u32] = bytemuck::cast_slice(&view);
+            for (&(la, lb), &distance) in batch_pairs.iter().zip(distances) {
+                results.push(DistanceResult::new(
+                    la as u64,
+                    lb as u64,
+                    lines[la].len(),
+                    lines[lb].len(),
+                    distance,
+                    &settings,
+                ));
+            }
+        }
+        readback_buffer.unmap();
+        tracing::info!(done = results.len(), "finished GPU batch");
+    }
+    Ok(results)
+}
@@ -0,0 +1,57 @@
+// Optimal string alignment distance of one pair per invocation.
+// Each invocation keeps the three DP rows required for transpositions in its own slice of `scratch`.
+
+struct Params {
+    pair_cnt: u32,
+    row_stride: u32,
+}
+
+@group(0) @binding(0) var<storage, read> text: array<u32>;
+@group(0) @binding(1) var<storage, read> offsets: array<u32>;
+@group(0) @binding(2) var<storage, read> lens: array<u32>;
+@group(0) @binding(3) var<storage, read> pairs: array<vec2<u32>>;
+@group(0) @binding(4) var<storage, read_write> scratch: array<u32>;
+@group(0) @binding(5) var<storage, read_write> distances: array<u32>;
+@group(0) @binding(6) var<uniform> params: Params;
+
+// the text is packed with four bytes per element
+fn byte_at(pos: u32) -> u32 {
+    return (text[pos >> 2u] >> ((pos & 3u) * 8u)) & 0xffu;
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let p = gid.x;
+    if (p >= params.pair_cnt) {
+        return;
+    }
+    let a_off = offsets[pairs[p].x];
+    let a_len = lens[pairs[p].x];
+    let b_off = offsets[pairs[p].y];
+    let b_len = lens[pairs[p].y];
+
+    // row i of the DP matrix is stored at slot i % 3
+    let stride = params.row_stride;
+    let base = p * stride * 3u;
+    for (var j = 0u; j <= b_len; j++) {
+        scratch[base + j] = j;
+    }
+    for (var i = 1u; i <= a_len; i++) {
+        let cur = base + (i % 3u) * stride;
+        let prev = base + ((i - 1u) % 3u) * stride;
+        let prev2 = base + ((i + 1u) % 3u) * stride;
+        scratch[cur] = i;
+        let a = byte_at(a_off + i - 1u);
+        for (var j = 1u; j <= b_len; j++) {
+            let b = byte_at(b_off + j - 1u);
+            let cost = select(1u, 0u, a == b);
+            var d = min(min(scratch[prev + j] + 1u, scratch[cur + j - 1u] + 1u), scratch[prev + j - 1u] + cost);
+            if (i > 1u && j > 1u && a == byte_at(b_off + j - 2u) && byte_at(a_off + i - 2u) == b) {
+                // transposition
+                d = min(d, scratch[prev2 + j - 2u] + 1u);
+            }
+            scratch[cur + j] = d;
+        }
+    }
+    distances[p] = scratch[base + (a_len % 3u) * stride + b_len];
+}
@@ -0,0 +1,125 @@
+//! Export of the similar pairs as a graph, e.g. for visualizing clusters of duplicates with Graphviz or Gephi.
+//!
+//! Nodes are the lines with at least one similar partner, edges the similar pairs weighted by their distance. The
+//! format is GraphML for paths ending in `.graphml` and DOT otherwise.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use dldist::engine::DistanceResult;
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_xml(text: &str) -> String {
+    // control characters are not allowed in XML 1.0 at all
+    text.replace(|c: char| c.is_control() && c != '\t', "\u{FFFD}")
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_dot<W: Write>(
+    writer: &mut W,
+    nodes: &[usize],
+    edges: &[&DistanceResult],
+    labels: &[String],
+    lines: &[Vec<u8>],
+) -> io::Result<()> {
+    writeln!(writer, "graph dldist {{")?;
+    for &node in nodes {
+        writeln!(
+            writer,
+            "  n{} [label=\"{}\", text=\"{}\"];",
+            node,
+            escape_dot(&labels[node]),
+            escape_dot(&String::from_utf8_lossy(&lines[node]))
+        )?;
+    }
+    for dr in edges {
+        writeln!(
+            writer,
+            "  n{} -- n{} [weight={}, label=\"{}\", norm={}];",
+            dr.line_a, dr.line_b, dr.dldist, dr.dldist, dr.normalized_dldist
+        )?;
+    }
+    writeln!(writer, "}}")
+}
+
+fn write_graphml<W: Write>(
+    writer: &mut W,
+    nodes: &[usize],
+    edges: &[&DistanceResult],
+    labels: &[String],
+    lines: &[Vec<u8>],
+) -> io::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="text" for="node" attr.name="text" attr.type="string"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="weight" for="edge" attr.name="weight" attr.type="int"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="norm" for="edge" attr.name="norm" attr.type="float"/>"#
+    )?;
+    writeln!(writer, r#"  <graph id="dldist" edgedefault="undirected">"#)?;
+    for &node in nodes {
+        writeln!(
+            writer,
+            r#"    <node id="n{}"><data key="label">{}</data><data key="text">{}</data></node>"#,
+            node,
+            escape_xml(&labels[node]),
+            escape_xml(&String::from_utf8_lossy(&lines[node]))
+        )?;
+    }
+    for dr in edges {
+        writeln!(
+            writer,
+            r#"    <edge source="n{}" target="n{}"><data key="weight">{}</data><data key="norm">{}</data></edge>"#,
+            dr.line_a, dr.line_b, dr.dldist, dr.normalized_dldist
+        )?;
+    }
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</graphml>")
+}
+
+/// Writes the graph of the given similar pairs and returns the amount of nodes.
+pub fn write(
+    path: &Path,
+    edges: &[&DistanceResult],
+    labels: &[String],
+    lines: &[Vec<u8>],
+) -> io::Result<usize> {
+    let mut nodes: Vec<usize> = edges
+        .iter()
+        .flat_map(|dr| [dr.line_a as usize, dr.line_b as usize])
+        .collect();
+    nodes.sort_unstable();
+    nodes.dedup();
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    if path.extension().is_some_and(|ext| ext == "graphml") {
+        write_graphml(&mut writer, &nodes, edges, labels, lines)?;
+    } else {
+        write_dot(&mut writer, &nodes, edges, labels, lines)?;
+    }
+    writer.flush()?;
+    Ok(nodes.len())
+}
@@ -0,0 +1,85 @@
+//! Merging of transitively similar lines into groups, e.g. for extracting the templates of log lines.
+
+/// Lines connected by similar pairs, directly or via other lines.
+pub struct Group {
+    /// The member with the most similar partners within the group, the lowest index on ties.
+    pub representative: usize,
+    /// Indices of all lines of the group in ascending order.
+    pub members: Vec<usize>,
+}
+
+/// Union-find structure over the line indices.
+struct DisjointSets {
+    parents: Vec<usize>,
+    sizes: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(cnt: usize) -> Self {
+        DisjointSets {
+            parents: (0..cnt).collect(),
+            sizes: vec![1; cnt],
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parents[i] != i {
+            // path halving keeps the trees flat
+            self.parents[i] = self.parents[self.parents[i]];
+            i = self.parents[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut root_a, mut root_b) = (self.find(a), self.find(b));
+        if root_a == root_b {
+            return;
+        }
+        if self.sizes[root_a] < self.sizes[root_b] {
+            std::mem::swap(&mut root_a, &mut root_b);
+        }
+        self.parents[root_b] = root_a;
+        self.sizes[root_a] += self.sizes[root_b];
+    }
+}
+
+/// Returns the groups of at least two lines which are connected by the given similar pairs, largest groups first.
+pub fn group_similar_lines<I>(lines_cnt: usize, similar_pairs: I) -> Vec<Group>
+where
+    I: Iterator<Item = (usize, usize)>,
+{
+    let mut sets = DisjointSets::new(lines_cnt);
+    let mut partner_cnts = vec![0usize; lines_cnt];
+    for (la, lb) in similar_pairs {
+        sets.union(la, lb);
+        partner_cnts[la] += 1;
+        partner_cnts[lb] += 1;
+    }
+
+    let mut members_by_root: Vec<Vec<usize>> = vec![Vec::new(); lines_cnt];
+    for i in 0..lines_cnt {
+        let root = sets.find(i);
+        members_by_root[root].push(i);
+    }
+    let mut groups: Vec<Group> = members_by_root
+        .into_iter()
+        .filter(|members| members.len() > 1)
+        .map(|members| Group {
+            // members are ascending, thus max_by_key would prefer the highest index on ties
+            representative: *members
+                .iter()
+                .rev()
+                .max_by_key(|&&m| partner_cnts[m])
+                .unwrap(),
+            members,
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.members
+            .len()
+            .cmp(&a.members.len())
+            .then_with(|| a.members[0].cmp(&b.members[0]))
+    });
+    groups
+}
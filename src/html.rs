@@ -0,0 +1,236 @@
+//! Standalone HTML report of the ranking, for sharing the results with people who do not use the terminal.
+//!
+//! The report holds a histogram of the distances of all pairs and a table of the ranked pairs, which can be sorted by
+//! clicking its headers and shows the differences of both lines of each pair highlighted.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use dldist::engine::DistanceResult;
+
+use crate::diff::{diff_lines, SegmentKind};
+
+/// Amount of bins of the histogram if the distances are not simply counted per value.
+const HISTOGRAM_BINS: usize = 20;
+const HISTOGRAM_WIDTH: usize = 600;
+const HISTOGRAM_HEIGHT: usize = 150;
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.5em; text-align: left; vertical-align: top; }
+th[data-type] { cursor: pointer; background: #eee; }
+td.number { text-align: right; }
+td.diff { font-family: monospace; white-space: pre-wrap; }
+del { background: #fdd; color: #a00; }
+ins { background: #dfd; color: #070; text-decoration: none; }
+rect { fill: #68a; }";
+
+const SCRIPT: &str = "document.querySelectorAll('th').forEach((th, column) => {
+  if (!th.dataset.type) return;
+  th.addEventListener('click', () => {
+    const body = th.closest('table').tBodies[0];
+    const ascending = th.dataset.order !== 'asc';
+    th.dataset.order = ascending ? 'asc' : 'desc';
+    const key = (row) => row.cells[column].textContent;
+    const compare = th.dataset.type === 'number'
+      ? (a, b) => key(a) - key(b)
+      : (a, b) => key(a).localeCompare(key(b), undefined, { numeric: true });
+    [...body.rows]
+      .sort((a, b) => (ascending ? compare(a, b) : compare(b, a)))
+      .forEach((row) => body.append(row));
+  });
+});";
+
+/// Settings of the report taken from the command line.
+pub struct Options<'a> {
+    pub title: &'a str,
+    pub normalize: bool,
+    /// Whether the normalized distances are similarities in percent.
+    pub as_similarity: bool,
+    pub precision: usize,
+    /// Amount of the best ranked pairs listed in the table.
+    pub rows_cnt: usize,
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Returns the bins of the histogram as their lower and upper bound along with the amount of distances within.
+pub fn histogram_bins(distances: &[f32], per_value: bool) -> Vec<(f32, f32, usize)> {
+    let Some(min) = distances.iter().copied().reduce(f32::min) else {
+        return Vec::new();
+    };
+    let max = distances.iter().copied().fold(min, f32::max);
+    // small integer distances are counted per value, which bins them evenly
+    let (start, width, bins_cnt) = if per_value && max - min < HISTOGRAM_BINS as f32 * 2.0 {
+        (min, 1.0, (max - min) as usize + 1)
+    } else if max > min {
+        (min, (max - min) / HISTOGRAM_BINS as f32, HISTOGRAM_BINS)
+    } else {
+        (min, 1.0, 1)
+    };
+    let mut counts = vec![0usize; bins_cnt];
+    for &d in distances {
+        let bin = ((d - start) / width) as usize;
+        counts[bin.min(bins_cnt - 1)] += 1;
+    }
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, cnt)| {
+            let lower = start + i as f32 * width;
+            (lower, lower + width, cnt)
+        })
+        .collect()
+}
+
+fn write_histogram<W: Write>(
+    writer: &mut W,
+    bins: &[(f32, f32, usize)],
+    per_value: bool,
+    precision: usize,
+) -> io::Result<()> {
+    let max_cnt = bins
+        .iter()
+        .map(|&(_, _, cnt)| cnt)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let bar_width = HISTOGRAM_WIDTH / bins.len().max(1);
+    writeln!(
+        writer,
+        "<svg width=\"{}\" height=\"{}\" role=\"img\">",
+        bar_width * bins.len(),
+        HISTOGRAM_HEIGHT
+    )?;
+    for (i, &(lower, upper, cnt)) in bins.iter().enumerate() {
+        let height = cnt * HISTOGRAM_HEIGHT / max_cnt;
+        let range = if per_value {
+            format!("{}", lower)
+        } else {
+            format!("{:.*} to {:.*}", precision, lower, precision, upper)
+        };
+        writeln!(
+            writer,
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"><title>{}: {} pairs</title></rect>",
+            i * bar_width,
+            HISTOGRAM_HEIGHT - height,
+            bar_width.saturating_sub(1).max(1),
+            height,
+            range,
+            cnt
+        )?;
+    }
+    writeln!(writer, "</svg>")
+}
+
+fn write_diff<W: Write>(writer: &mut W, line_a: &[u8], line_b: &[u8]) -> io::Result<()> {
+    for segment in diff_lines(line_a, line_b) {
+        let text = escape_html(&segment.text);
+        match segment.kind {
+            SegmentKind::Equal => write!(writer, "{}", text)?,
+            SegmentKind::Removed => write!(writer, "<del>{}</del>", text)?,
+            SegmentKind::Added => write!(writer, "<ins>{}</ins>", text)?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes the report of the results, which have to be sorted already.
+pub fn write(
+    path: &Path,
+    results: &[DistanceResult],
+    labels: &[String],
+    lines: &[Vec<u8>],
+    options: Options,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let title = escape_html(options.title);
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(
+        writer,
+        "<title>Damerau-Levenshtein distances of {}</title>",
+        title
+    )?;
+    writeln!(writer, "<style>\n{}\n</style>\n</head>\n<body>", STYLE)?;
+    writeln!(
+        writer,
+        "<h1>Damerau-Levenshtein distances of {}</h1>",
+        title
+    )?;
+
+    let rows_cnt = options.rows_cnt.min(results.len());
+    writeln!(
+        writer,
+        "<h2>Histogram of the {} of all {} pairs</h2>",
+        if options.as_similarity {
+            "similarities"
+        } else if options.normalize {
+            "normalized distances"
+        } else {
+            "distances"
+        },
+        results.len()
+    )?;
+    let distances: Vec<f32> = results
+        .iter()
+        .map(|dr| {
+            if options.normalize {
+                dr.normalized_dldist
+            } else {
+                dr.dldist as f32
+            }
+        })
+        .collect();
+    let bins = histogram_bins(&distances, !options.normalize);
+    write_histogram(&mut writer, &bins, !options.normalize, options.precision)?;
+
+    writeln!(
+        writer,
+        "<h2>Top {} of {} pairs</h2>",
+        rows_cnt,
+        results.len()
+    )?;
+    writeln!(writer, "<table>\n<thead>\n<tr>")?;
+    write!(
+        writer,
+        "<th data-type=\"number\">#</th><th data-type=\"text\">Line A</th><th data-type=\"text\">Line B</th>\
+         <th data-type=\"number\">Dist.</th>"
+    )?;
+    if options.as_similarity {
+        write!(writer, "<th data-type=\"number\">Similarity (%)</th>")?;
+    } else if options.normalize {
+        write!(writer, "<th data-type=\"number\">Norm.</th>")?;
+    }
+    writeln!(writer, "<th>Diff</th>\n</tr>\n</thead>\n<tbody>")?;
+    for (rank, dr) in results.iter().take(rows_cnt).enumerate() {
+        let (la, lb) = (dr.line_a as usize, dr.line_b as usize);
+        write!(
+            writer,
+            "<tr><td class=\"number\">{}</td><td>{}</td><td>{}</td><td class=\"number\">{}</td>",
+            rank + 1,
+            escape_html(&labels[la]),
+            escape_html(&labels[lb]),
+            dr.dldist
+        )?;
+        if options.normalize {
+            write!(
+                writer,
+                "<td class=\"number\">{:.*}</td>",
+                options.precision, dr.normalized_dldist
+            )?;
+        }
+        write!(writer, "<td class=\"diff\">")?;
+        write_diff(&mut writer, &lines[la], &lines[lb])?;
+        writeln!(writer, "</td></tr>")?;
+    }
+    writeln!(writer, "</tbody>\n</table>")?;
+    writeln!(writer, "<script>\n{}\n</script>\n</body>\n</html>", SCRIPT)?;
+    writer.flush()
+}
@@ -0,0 +1,86 @@
+//! List of known and accepted pairs which are suppressed from the results, e.g. as a baseline for CI runs.
+//!
+//! Every line of the file holds one pair as `<a>,<b>`, where each side is either a label as printed in the results
+//! (the line number, `path:line` for multiple files or the path when comparing files) or `0x` followed by the 16 hex
+//! digits of the 64-bit FNV-1a hash of the content. Hashes keep matching when lines are moved. The order within a pair
+//! does not matter, empty lines and lines starting with `#` are skipped.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const HASH_PREFIX: &str = "0x";
+const COMMENT_PREFIX: char = '#';
+
+pub struct IgnoreList {
+    labels: HashSet<(String, String)>,
+    hashes: HashSet<(u64, u64)>,
+}
+
+/// Returns the 64-bit FNV-1a hash of the content, which unlike the std hashers is stable across builds.
+pub fn content_hash(content: &[u8]) -> u64 {
+    extend_hash(0xcbf2_9ce4_8422_2325, content)
+}
+
+/// Continues the FNV-1a hash of [`content_hash`] with further content.
+pub fn extend_hash(hash: u64, content: &[u8]) -> u64 {
+    content.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn ordered<T: Ord>(a: T, b: T) -> (T, T) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+impl IgnoreList {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let mut list = IgnoreList {
+            labels: HashSet::new(),
+            hashes: HashSet::new(),
+        };
+        for (i, file_line) in fs::read_to_string(path)?.lines().enumerate() {
+            let file_line = file_line.trim();
+            if file_line.is_empty() || file_line.starts_with(COMMENT_PREFIX) {
+                continue;
+            }
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid pair in line {}: {}", i + 1, file_line),
+                )
+            };
+            // labels may contain commas themselves, while hashes never do
+            let (a, b) = file_line.rsplit_once(',').ok_or_else(invalid)?;
+            let (a, b) = (a.trim(), b.trim());
+            match (a.strip_prefix(HASH_PREFIX), b.strip_prefix(HASH_PREFIX)) {
+                (Some(hash_a), Some(hash_b)) => {
+                    let hash_a = u64::from_str_radix(hash_a, 16).map_err(|_| invalid())?;
+                    let hash_b = u64::from_str_radix(hash_b, 16).map_err(|_| invalid())?;
+                    list.hashes.insert(ordered(hash_a, hash_b));
+                }
+                (None, None) => {
+                    list.labels.insert(ordered(a.to_string(), b.to_string()));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(list)
+    }
+
+    pub fn contains(&self, label_a: &str, label_b: &str, line_a: &[u8], line_b: &[u8]) -> bool {
+        (!self.labels.is_empty()
+            && self
+                .labels
+                .contains(&ordered(label_a.to_string(), label_b.to_string())))
+            || (!self.hashes.is_empty()
+                && self
+                    .hashes
+                    .contains(&ordered(content_hash(line_a), content_hash(line_b))))
+    }
+}
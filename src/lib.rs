@@ -0,0 +1,14 @@
+//! Optimal string alignment (restricted Damerau-Levenshtein) distances between lines, as calculated by the
+//! `dldist-file` binary.
+
+#[cfg(feature = "capi")]
+mod capi;
+pub mod engine;
+pub mod osa;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod prefix;
+#[cfg(feature = "python")]
+mod python;
+pub mod simd;
+#[cfg(feature = "wasm")]
+mod wasm;
@@ -1,161 +1,607 @@
-use std::cmp::Ordering;
-use std::fs::File;
-use std::io::{self, BufRead};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
 use std::num::NonZero;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Condvar, Mutex};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use clap::Parser;
-use num_traits::PrimInt;
-use threadpool::ThreadPool;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum};
+use dldist::engine::{
+    auto_thread_num, calculate_osa_distances, cross_pairs, distance_result_order, focus_pairs,
+    is_similarity, selected_pairs, sort_distance_results, window_combinations_count,
+    DistanceResult, Normalization, Settings, Tuning, AS_SIMILARITY, NORMALIZATION, THREAD_NUM,
+    TUNING,
+};
+use dldist::osa::{Algorithm, CostMatrix, Kernel, TypoModel, ALGORITHM, COST_MATRIX, KERNEL};
+use dldist::{prefix, simd};
+use tracing::{info, Level};
+use unicode_normalization::UnicodeNormalization;
 
-struct DistanceResult {
-    line_a: u32,
-    line_b: u32,
-    _mean_line_len: f32,
-    dldist: u32,
-    normalized_dldist: f32,
-}
+use crate::changes::LineChange;
+use crate::modes::Mode;
+
+#[cfg(feature = "archive")]
+mod archive;
+mod bench;
+mod changes;
+mod checkpoint;
+mod config;
+mod diff;
+mod external;
+#[cfg(feature = "gpu")]
+mod gpu;
+mod graph;
+mod groups;
+mod html;
+mod ignore;
+mod matrix;
+mod modes;
+#[cfg(feature = "parquet")]
+mod parquet_sink;
+mod phonetic;
+mod scores;
+mod serve;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod store;
+#[cfg(feature = "tui")]
+mod tui;
 
-const NUM_PRINT_ALL: u16 = 0;
+const NUM_PRINT_ALL: u64 = 0;
 const NUM_ALL_THREADS_AVAILBLE: usize = 0;
 
-static VERBOSE: Mutex<bool> = Mutex::new(false);
-static THREAD_NUM: Mutex<usize> = Mutex::new(1);
+/// Pair count from which on a run is considered large and its runtime and memory usage is estimated beforehand.
+const LARGE_PAIR_CNT: u64 = 10_000_000;
+const ESTIMATION_SAMPLE_CNT: usize = 10_000;
 
-/// Returns an Iterator to the Reader of the lines of the file.
-/// Preserves order and count of the raw file lines.
-fn read_lines<P>(filename: P) -> io::Result<Vec<String>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    let lines = io::BufReader::new(file).lines();
-    let lines_filtered: Vec<_> = lines
-        .map(|i| i.expect(""))
-        // .filter(|x| !x.trim().is_empty()) // -> do not! filter for emtpy lines here as otherwise the line numbers would not match those of the raw input file!
-        .collect();
-    Ok(lines_filtered)
+/// Unit of text that is compared against each other.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Unit {
+    /// Every line of every input file is one item.
+    Line,
+    /// Every input file as a whole is one item.
+    File,
 }
 
-/// Returns the amount of pair-combinations
-fn pair_combinations_count<T>(num: T) -> T
-where
-    T: PrimInt + std::convert::From<u32>,
-{
-    if num < 2u32.into() {
-        0u32.into()
-    } else {
-        (num * (num - 1u32.into())) / 2u32.into()
-    }
+/// Phonetic encoding of the words of a line before comparing it.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Phonetic {
+    /// American Soundex, a letter followed by three digits per word.
+    Soundex,
+    /// Original Metaphone by Lawrence Philips.
+    Metaphone,
 }
 
-// implementation inspired from: https://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance#Optimal_string_alignment_distance
-fn calculate_osa_distance_between_two_strings(str_a: &str, str_b: &str) -> u32 {
-    let mut dist = vec![vec![0u32; str_b.len() + 1]; str_a.len() + 1]; // making sure indexing is in correct order
+/// Unicode normalization form applied to the lines before comparing them.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum UnicodeForm {
+    /// Canonical composition, e.g. `e` followed by a combining acute accent becomes `é`.
+    Nfc,
+    /// Canonical decomposition, e.g. `é` becomes `e` followed by a combining acute accent.
+    Nfd,
+    /// Compatibility composition, which additionally folds e.g. ligatures and full-width forms.
+    Nfkc,
+    /// Compatibility decomposition.
+    Nfkd,
+}
 
-    for i in 0..=str_a.len() {
-        dist[i][0] = i as u32;
-    }
-    dist[0] = (0..=str_b.len() as u32).collect();
+/// Returns the line in the given normalization form. Lines which are not valid UTF-8 are kept as they are.
+fn normalize_unicode(line: &[u8], form: UnicodeForm) -> Vec<u8> {
+    let Ok(text) = std::str::from_utf8(line) else {
+        return line.to_vec();
+    };
+    let normalized: String = match form {
+        UnicodeForm::Nfc => text.nfc().collect(),
+        UnicodeForm::Nfd => text.nfd().collect(),
+        UnicodeForm::Nfkc => text.nfkc().collect(),
+        UnicodeForm::Nfkd => text.nfkd().collect(),
+    };
+    normalized.into_bytes()
+}
 
-    // using bytes instead of chars since we can not be sure of only UTF-8 characters being included in the file
-    let mut a_prior: u8 = 0x00; // actual initial value does not matter
-    let mut b_prior: u8 = 0x00; // actual initial value does not matter
-    for (i, a) in str_a.bytes().enumerate() {
-        for (j, b) in str_b.bytes().enumerate() {
-            let cost: u32 = if a == b { 0 } else { 1 };
-            dist[i + 1][j + 1] = (dist[i][j + 1] + 1) // deletion
-                .min(dist[i + 1][j] + 1) // insertion
-                .min(dist[i][j] + cost); // substitution
+/// Handling of numbers within the lines before comparing them.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Numbers {
+    /// Compare digits like any other character.
+    Keep,
+    /// Replace every run of digits by a single `#`.
+    Mask,
+    /// Replace every run of digits by `#` followed by its digit count, so that only numbers of a different magnitude
+    /// differ.
+    Bucket,
+}
 
-            if i > 0 && j > 0 && a == b_prior && a_prior == b {
-                // transposition
-                dist[i + 1][j + 1] = dist[i + 1][j + 1].min(dist[i - 1][j - 1] + 1);
+/// Replaces the runs of digits of the line according to the selected handling.
+fn replace_numbers(line: &[u8], numbers: Numbers) -> Vec<u8> {
+    if numbers == Numbers::Keep {
+        return line.to_vec();
+    }
+    let mut replaced = Vec::with_capacity(line.len());
+    let mut digit_cnt = 0usize;
+    for (i, &c) in line.iter().enumerate() {
+        if c.is_ascii_digit() {
+            digit_cnt += 1;
+            if line.get(i + 1).is_some_and(u8::is_ascii_digit) {
+                continue;
             }
-
-            b_prior = b;
+            replaced.push(b'#');
+            if numbers == Numbers::Bucket {
+                replaced.extend_from_slice(digit_cnt.to_string().as_bytes());
+            }
+            digit_cnt = 0;
+        } else {
+            replaced.push(c);
         }
-        a_prior = a;
     }
+    replaced
+}
+
+/// Encodes every whitespace-separated word of the line phonetically.
+fn encode_phonetically(line: &[u8], phonetic: Phonetic) -> Vec<u8> {
+    let encode = match phonetic {
+        Phonetic::Soundex => phonetic::soundex,
+        Phonetic::Metaphone => phonetic::metaphone,
+    };
+    line.split(|c| c.is_ascii_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            // keeping words without any letters like numbers, as they have no phonetic code
+            let code = encode(word);
+            if code.is_empty() {
+                word.to_vec()
+            } else {
+                code
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(&b' ')
+}
+
+/// Returns the lines as they are compared, after applying the selected replacements and encodings. The original
+/// lines are kept for printing them.
+fn prepare_lines<'a>(lines: &'a [Vec<u8>], args: &Arguments) -> Cow<'a, [Vec<u8>]> {
+    let max_len = match args.max_line_len {
+        Some(len) if args.long_lines == LongLines::Truncate => len.get(),
+        _ => usize::MAX,
+    };
+    if args.unicode_normalize.is_none()
+        && args.numbers == Numbers::Keep
+        && args.phonetic.is_none()
+        && lines.iter().all(|line| line.len() <= max_len)
+    {
+        return Cow::Borrowed(lines);
+    }
+    Cow::Owned(
+        lines
+            .iter()
+            .map(|line| {
+                let normalized;
+                let line = match args.unicode_normalize {
+                    Some(form) => {
+                        normalized = normalize_unicode(line, form);
+                        &normalized
+                    }
+                    None => line,
+                };
+                let line = replace_numbers(&line[..line.len().min(max_len)], args.numbers);
+                match args.phonetic {
+                    Some(phonetic) => encode_phonetically(&line, phonetic),
+                    None => line,
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Handling of lines longer than --max-line-len.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LongLines {
+    /// Leave out all pairs containing a long line.
+    Skip,
+    /// Only compare the first N bytes of a long line.
+    Truncate,
+    /// Abort with an error naming the first long line.
+    Error,
+}
+
+/// Aggregate of the distances of a line to all others by which --outliers ranks the lines.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutlierScore {
+    /// Mean distance to all compared lines.
+    Mean,
+    /// Distance to the closest compared line, the maximum similarity with the similarity normalization.
+    Min,
+}
+
+/// Format of the log messages enabled by --verbose.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
 
-    if *VERBOSE.lock().unwrap() {
-        // print beautified 2D-matrix
-        println!("{}", format!("{:?}", dist).replace("], [", "],\n["));
+/// Sets up logging to stderr, where warnings are always logged, `-v` adds progress details, `-vv` every calculated
+/// pair and `-vvv` even the DP matrices of the scalar kernel.
+fn init_logging(verbosity: u8, format: LogFormat) {
+    let level = match verbosity {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        // reporting failed writes to stderr would fail as well, panicking the worker threads
+        .log_internal_errors(false);
+    match format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
     }
+}
 
-    return dist[str_a.len()][str_b.len()];
+/// Format of the calculated results.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Sorted and human-readable list printed to stdout.
+    Text,
+    /// Unsorted columnar file with every pair, written to --output.
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// Standalone report written to --output, with a histogram of the distances of all pairs and a sortable table of
+    /// the ranked pairs with their differences highlighted.
+    Html,
 }
 
-use std::sync::mpsc::channel;
-fn calculate_osa_distances(lines: &Vec<String>) -> Vec<DistanceResult> {
-    let lines_cnt = lines.len();
+/// Handling of input which is not valid UTF-8.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InvalidUtf8 {
+    /// Abort with an error naming the offending line.
+    Error,
+    /// Replace invalid sequences with the U+FFFD replacement character.
+    Lossy,
+    /// Compare the raw bytes without decoding them at all.
+    Bytes,
+}
 
-    let pool = ThreadPool::new(*THREAD_NUM.lock().unwrap());
+/// Character encoding of the input files.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InputEncoding {
+    #[value(name = "utf-8")]
+    Utf8,
+    /// ISO-8859-1, decoded as its superset Windows-1252.
+    Latin1,
+    #[value(name = "utf-16le")]
+    Utf16Le,
+    #[value(name = "utf-16be")]
+    Utf16Be,
+    /// Detect the encoding by its byte order mark, falling back to UTF-8.
+    Auto,
+}
+
+/// Converts the raw content into UTF-8. UTF-8 content is passed through unchecked, as it is handled according to
+/// --invalid-utf8 afterwards, while malformed sequences of other encodings are replaced.
+fn decode_encoding(raw: Vec<u8>, encoding: InputEncoding) -> Vec<u8> {
+    let enc = match encoding {
+        InputEncoding::Utf8 => return raw,
+        InputEncoding::Latin1 => encoding_rs::WINDOWS_1252,
+        InputEncoding::Utf16Le => encoding_rs::UTF_16LE,
+        InputEncoding::Utf16Be => encoding_rs::UTF_16BE,
+        InputEncoding::Auto => match encoding_rs::Encoding::for_bom(&raw) {
+            Some((enc, bom_len)) if enc == encoding_rs::UTF_8 => return raw[bom_len..].to_vec(),
+            Some((enc, _)) => enc,
+            None => return raw,
+        },
+    };
+    // also strips a byte order mark
+    let (text, _, _) = enc.decode(&raw);
+    text.into_owned().into_bytes()
+}
 
-    let rx = {
-        // required so that tx on main thread is dropped and rx.iter() does not block
-        let (tx, rx) = channel::<DistanceResult>();
+/// Checks or converts the raw bytes of a line (or file) according to the selected handling of invalid UTF-8.
+fn decode_text(raw: Vec<u8>, invalid_utf8: InvalidUtf8) -> Result<Vec<u8>, std::str::Utf8Error> {
+    match invalid_utf8 {
+        InvalidUtf8::Error => {
+            std::str::from_utf8(&raw)?;
+            Ok(raw)
+        }
+        InvalidUtf8::Lossy => match String::from_utf8(raw) {
+            Ok(text) => Ok(text.into_bytes()),
+            Err(error) => Ok(String::from_utf8_lossy(error.as_bytes())
+                .into_owned()
+                .into_bytes()),
+        },
+        InvalidUtf8::Bytes => Ok(raw),
+    }
+}
 
-        // notifying variables to wake up main thread
-        let pair = Arc::new((Mutex::new(()), Condvar::new()));
-        let (lock, cvar) = &*pair;
+/// Byte range of a line within its input, without the line ending.
+#[derive(Clone, Copy)]
+struct LineSpan {
+    offset: u64,
+    len: usize,
+}
 
-        for la in 0..lines_cnt {
-            for lb in la..lines_cnt {
-                if la == lb {
-                    // ignore self-comparison
-                    continue;
-                }
-                let line_a = lines[la].clone();
-                let line_b = lines[lb].clone();
-                let tx_child = tx.clone();
-                let pair_child = Arc::clone(&pair);
-                pool.execute(move || {
-                    let distance = calculate_osa_distance_between_two_strings(&line_a, &line_b);
-                    let mean_line_length = ((line_a.len() as f32) + (line_b.len() as f32)) * 0.5f32;
-                    tx_child
-                        .send(DistanceResult {
-                            line_a: la as u32,
-                            line_b: lb as u32,
-                            _mean_line_len: mean_line_length,
-                            dldist: distance,
-                            normalized_dldist: (distance as f32) / mean_line_length,
-                        })
-                        .unwrap();
-
-                    // We notify the condvar that we are done with calculating.
-                    let (lock_child, cvar_child) = &*pair_child;
-                    let _guard = lock_child.lock().unwrap();
-                    cvar_child.notify_one();
-                });
+/// Splits the content into lines like [`BufRead::lines`] does, but without requiring the lines to be valid UTF-8.
+/// Preserves order and count of the raw lines.
+fn split_lines<R: BufRead>(
+    reader: R,
+    invalid_utf8: InvalidUtf8,
+) -> io::Result<(Vec<Vec<u8>>, Vec<LineSpan>)> {
+    let mut offset = 0u64;
+    reader
+        .split(b'\n')
+        // .filter(|x| !x.trim().is_empty()) // -> do not! filter for emtpy lines here as otherwise the line numbers would not match those of the raw input file!
+        .enumerate()
+        .map(|(i, raw)| {
+            let mut raw = raw?;
+            let line_offset = offset;
+            offset += raw.len() as u64 + 1;
+            if raw.last() == Some(&b'\r') {
+                raw.pop();
+            }
+            let span = LineSpan {
+                offset: line_offset,
+                len: raw.len(),
+            };
+            let line = decode_text(raw, invalid_utf8).map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "line {} is not valid UTF-8 ({}), see --invalid-utf8",
+                        i + 1,
+                        error
+                    ),
+                )
+            })?;
+            Ok((line, span))
+        })
+        .collect()
+}
 
-                {
-                    // This prevents from spamming the queue and thus the memory.
-                    // That way it makes sure the current+queued jobs are twice the set thread count.
-                    let mut _guard = lock.lock().unwrap();
-                    while pool.queued_count() >= pool.max_count() {
-                        _guard = cvar.wait(_guard).unwrap();
+/// Returns the lines of the file.
+/// Preserves order and count of the raw file lines.
+fn read_lines<P>(
+    filename: P,
+    encoding: InputEncoding,
+    invalid_utf8: InvalidUtf8,
+) -> io::Result<(Vec<Vec<u8>>, Vec<LineSpan>)>
+where
+    P: AsRef<Path>,
+{
+    if encoding == InputEncoding::Utf8 {
+        let file = File::open(filename)?;
+        return split_lines(io::BufReader::new(file), invalid_utf8);
+    }
+    let content = decode_encoding(fs::read(filename)?, encoding);
+    split_lines(content.as_slice(), invalid_utf8)
+}
+
+/// Returns the lines of the file as stored at the given git revision.
+fn read_git_lines(
+    path: &Path,
+    rev: &str,
+    encoding: InputEncoding,
+    invalid_utf8: InvalidUtf8,
+) -> io::Result<Vec<Vec<u8>>> {
+    // running git from within the file's directory allows for addressing the file relative to it
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::other("input path does not name a file"))?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("{}:./{}", rev, file_name.to_string_lossy()))
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    let content = decode_encoding(output.stdout, encoding);
+    split_lines(content.as_slice(), invalid_utf8).map(|(lines, _)| lines)
+}
+
+/// Returns the files to read in. Directories are walked recursively and their files are sorted by path
+/// so that the resulting order does not depend on the file system.
+fn collect_input_files(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut dir_files = Vec::new();
+            let mut dirs = vec![path.clone()];
+            while let Some(dir) = dirs.pop() {
+                for entry in fs::read_dir(dir)? {
+                    let entry_path = entry?.path();
+                    if entry_path.is_dir() {
+                        dirs.push(entry_path);
+                    } else {
+                        dir_files.push(entry_path);
                     }
                 }
             }
+            dir_files.sort();
+            files.append(&mut dir_files);
+        } else {
+            files.push(path.clone());
         }
-        rx
-    };
-    pool.join();
+    }
+    Ok(files)
+}
+
+/// Returns the duration in a human-readable unit.
+fn format_duration(secs: f64) -> String {
+    if secs < 60f64 {
+        format!("{:.1}s", secs)
+    } else if secs < 3600f64 {
+        format!("{:.1}min", secs / 60f64)
+    } else {
+        format!("{:.1}h", secs / 3600f64)
+    }
+}
+
+/// Warns that the --timeout stopped the calculation before all pairs have been evaluated.
+fn print_timeout_warning(evaluated_cnt: u64, combinations_cnt: u64) {
+    println!(
+        "WARN: Timed out, only {:.2}% of pairs evaluated ({} of {}).",
+        100f64 * evaluated_cnt as f64 / combinations_cnt as f64,
+        evaluated_cnt,
+        combinations_cnt
+    );
+}
+
+/// Estimates the runtime by timing a sample of pairs spread over the input and prints it together with the memory
+/// required for holding all results.
+fn print_large_run_estimate(lines: &[Vec<u8>], combinations_cnt: u64, results_in_memory: bool) {
+    let lines_cnt = lines.len();
+    let sample_cnt = ESTIMATION_SAMPLE_CNT.min(lines_cnt / 2);
+    let sample_pairs = (0..sample_cnt).map(|k| {
+        let la = k * (lines_cnt / 2) / sample_cnt;
+        (la, lines_cnt - 1 - la)
+    });
+    let start_time = Instant::now();
+    calculate_osa_distances(lines, lines, sample_pairs);
+    let secs_per_pair = start_time.elapsed().as_secs_f64() / sample_cnt.max(1) as f64;
+    println!(
+        "WARN: This is a large run, estimated to take about {} with {} threads.",
+        format_duration(secs_per_pair * combinations_cnt as f64),
+        *THREAD_NUM.lock().unwrap()
+    );
+    if results_in_memory {
+        let estimated_bytes =
+            combinations_cnt as f64 * std::mem::size_of::<DistanceResult>() as f64;
+        println!(
+            "WARN: Holding all results requires about {:.2} GiB of memory.",
+            estimated_bytes / (1u64 << 30) as f64
+        );
+    }
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the completion script for the given shell to stdout.
+    Completions { shell: clap_complete::Shell },
+    /// Time the calculation for synthetic lines with different algorithms and thread counts.
+    Bench(bench::BenchArguments),
+    /// Answer distance queries over HTTP with JSON, see the `serve` module docs for the endpoints.
+    Serve(serve::ServeArguments),
+}
+
+/// Placeholders available in a --template.
+const TEMPLATE_FIELDS: &[&str] = &[
+    "line_a", "line_b", "text_a", "text_b", "offset_a", "len_a", "offset_b", "len_b", "dist",
+    "norm",
+];
 
-    rx.iter().collect()
+/// Replaces the known `{field}` placeholders of the template with their values, everything else is kept as is.
+fn render_template(template: &str, value: impl Fn(&str) -> String) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let field = rest[1..]
+            .find('}')
+            .map(|end| &rest[1..end + 1])
+            .filter(|field| TEMPLATE_FIELDS.contains(field));
+        match field {
+            Some(field) => {
+                rendered.push_str(&value(field));
+                rest = &rest[field.len() + 2..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Parses a cost given in edits into half edits, e.g. `1.5` into 3.
+fn parse_half_edits(value: &str) -> Result<u32, String> {
+    let cost: f32 = value
+        .parse()
+        .map_err(|_| format!("'{value}' is not a number"))?;
+    let half_edits = cost * 2.0;
+    if !(0.0..=u16::MAX as f32).contains(&half_edits) || half_edits.fract() != 0.0 {
+        return Err(format!("'{value}' is not a non-negative multiple of 0.5"));
+    }
+    Ok(half_edits as u32)
+}
+
+/// Parses a per-pair output template, turning the `\t` and `\n` escapes into tabs and newlines.
+fn parse_template(value: &str) -> Result<String, String> {
+    let template = value.replace("\\t", "\t").replace("\\n", "\n");
+    let unknown = template
+        .split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}'))
+        .map(|(field, _)| field)
+        .find(|field| !TEMPLATE_FIELDS.contains(field));
+    match unknown {
+        Some(field) => Err(format!(
+            "unknown placeholder '{{{}}}', available are {{{}}}",
+            field,
+            TEMPLATE_FIELDS.join("}, {")
+        )),
+        None => Ok(template),
+    }
+}
+
+/// Parses a percentage within (0, 100].
+fn parse_percent(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(percent) if percent > 0f64 && percent <= 100f64 => Ok(percent),
+        _ => Err(format!("'{}' is not a percentage within (0, 100]", value)),
+    }
 }
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    after_help = "Defaults for all options can be set by their long name in a dldist.toml config file (searched for in \
+                  the current directory and its parents) or by DLDIST_<OPTION> environment variables, e.g. \
+                  `thread-num = 4` or DLDIST_THREAD_NUM=4. Options on the command line take precedence over the \
+                  environment, which takes precedence over the config file. Defaults conflicting with an option on \
+                  the command line are left out.",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Arguments {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     // TODO: also accept conent from stdin ('-')
-    /// Input raw text file to analyse.
+    /// Input raw text files to analyse. Directories are searched recursively for files.
     #[clap(required = true)]
-    input_file: PathBuf,
+    input_files: Vec<PathBuf>,
+
+    /// Compare single lines or whole files with each other.
+    #[arg(short = 'u', long, value_enum, default_value_t = Unit::Line)]
+    unit: Unit,
+
+    /// Character encoding of the input files.
+    #[arg(short = 'e', long, value_enum, default_value_t = InputEncoding::Utf8)]
+    encoding: InputEncoding,
+
+    /// How to handle input which is not valid UTF-8. The distances are calculated on bytes (not characters) anyway.
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = InvalidUtf8::Error)]
+    invalid_utf8: InvalidUtf8,
 
     /// List the results in descending order (default is ascending for viewing equal-like lines first)
     #[arg(short = 'd', long)]
@@ -163,147 +609,2082 @@ struct Arguments {
 
     /// List only the top N pairs of lines depending on order direction. Set to 0 to list all pairs.
     #[arg(short = 'n', long, default_value_t = 10)]
-    n_pairs: u16,
+    n_pairs: u64,
+
+    /// List only the top P percent of all pairs depending on order direction instead of a fixed count. Takes
+    /// precedence over --n-pairs.
+    #[arg(long, value_name = "P", value_parser = parse_percent)]
+    top_percent: Option<f64>,
 
-    /// Normalizes the resulting distance by the mean lengths of the lines in the pair. This value is used for sorted output instead.
+    /// Normalizes the resulting distance by the lengths of the lines in the pair (see --normalize-by). This value is used for sorted output instead.
     #[arg(long)]
     normalize: bool,
 
+    /// Strategy used for normalizing the distance. Implies --normalize.
+    #[arg(long, value_enum, value_name = "STRATEGY")]
+    normalize_by: Option<Normalization>,
+
+    /// Show the similarity of every pair from 0 to 100% instead of its distance, e.g. "96.88% similar", which is
+    /// derived from the normalized distance (see --normalize-by) and capped at 0%. Implies --normalize. The most
+    /// similar pairs are listed first and --threshold is the minimum similarity in percent. As --precision counts
+    /// the digits of the fraction, two less are shown after the decimal point.
+    #[arg(long)]
+    as_similarity: bool,
+
+    /// Compare each line only against the K lines following it instead of every other line.
+    /// Useful for sorted or chronologically ordered files where similar lines are close to each other.
+    #[arg(short = 'w', long, value_name = "K")]
+    window: Option<NonZero<usize>>,
+
+    /// Bring the lines into the given Unicode normalization form before comparing them, so that e.g. composed and
+    /// decomposed accents compare as equal. The original lines are printed.
+    #[arg(long, value_enum, value_name = "FORM")]
+    unicode_normalize: Option<UnicodeForm>,
+
+    /// Encode every whitespace-separated word phonetically before comparing the lines, e.g. for matching misspelled
+    /// names. The original lines are printed.
+    #[arg(long, value_enum, value_name = "ENCODING")]
+    phonetic: Option<Phonetic>,
+
+    /// Handling of runs of digits before comparing the lines, e.g. for lines only differing in counters or IDs.
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = Numbers::Keep)]
+    numbers: Numbers,
+
+    /// Maximum length of a line (or file) in bytes. Every pair requires a DP matrix of the product of both lengths,
+    /// so longer ones are handled according to --long-lines.
+    #[arg(long, value_name = "N")]
+    max_line_len: Option<NonZero<usize>>,
+
+    /// Handling of lines longer than --max-line-len.
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = LongLines::Truncate)]
+    long_lines: LongLines,
+
+    /// Leave lines (or files) shorter than N bytes out of the comparison, e.g. braces and separators. The remaining
+    /// lines keep their numbers.
+    #[arg(long, value_name = "N")]
+    min_len: Option<usize>,
+
+    /// Leave lines (or files) longer than N bytes out of the comparison. Unlike --max-line-len, it is no limit but
+    /// only selects the lines of interest.
+    #[arg(long, value_name = "N")]
+    max_len: Option<usize>,
+
+    /// Only calculate and report the pairs containing line N, counted from 1 over all input files in order. Can be
+    /// given multiple times.
+    #[arg(long = "focus-line", value_name = "N")]
+    focus_lines: Vec<NonZero<usize>>,
+
+    /// Suppress the known and accepted pairs listed in this file from the results. Each line holds a pair as
+    /// `<a>,<b>` of two labels as printed in the results or of two 64-bit FNV-1a hashes of the contents like
+    /// `0x0123456789abcdef`, which keep matching when lines are moved.
+    #[arg(long, value_name = "PATH")]
+    ignore_pairs: Option<PathBuf>,
+
+    /// Periodically persist the completed pairs to this file, so that an aborted run can be continued with --resume.
+    #[arg(long, value_name = "PATH")]
+    checkpoint: Option<PathBuf>,
+
+    /// Continue the run stored in the --checkpoint file instead of starting over.
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+
+    /// Record the hashes of the lines and the distances of their pairs in this file. Subsequent runs against an
+    /// updated input only calculate the pairs involving new or changed lines and take the others from the file,
+    /// which is then replaced with the results of the current run.
+    #[arg(long, value_name = "PATH", conflicts_with = "checkpoint")]
+    store: Option<PathBuf>,
+
+    /// Output format of the results.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// File to write the results to, required by non-text formats.
+    #[arg(
+        short = 'o',
+        long,
+        value_name = "PATH",
+        required_if_eq_any([("format", "parquet"), ("format", "html")])
+    )]
+    output: Option<PathBuf>,
+
+    /// Keep only the positions of the lines in memory and read them in block by block, spilling sorted results into
+    /// temporary files (in $TMPDIR) which are merged for the output. For inputs larger than the memory.
+    #[arg(
+        long,
+        conflicts_with_all = ["checkpoint", "store", "focus_lines", "ignore_pairs", "best_match", "groups", "outliers", "line_scores", "graph", "git_base", "output", "timeout"]
+    )]
+    low_memory: bool,
+
+    /// Stop scheduling new pairs after the given amount of seconds and report the results of the pairs evaluated so
+    /// far. Combined with --checkpoint, the remaining pairs can be calculated later on with --resume.
+    #[arg(long)]
+    timeout: Option<NonZero<u64>>,
+
+    /// Stream every calculated pair into the `pairs` table of this SQLite database instead of sorting and printing
+    /// them. The compared lines are stored in the `lines` table.
+    #[cfg(feature = "sqlite")]
+    #[arg(
+        long,
+        value_name = "DB",
+        conflicts_with_all = ["checkpoint", "store", "low_memory", "stream", "git_base", "paired", "queries"]
+    )]
+    sqlite: Option<PathBuf>,
+
+    /// Calculate the distances on the GPU in large batches instead of on the CPU threads.
+    #[cfg(feature = "gpu")]
+    #[arg(
+        long,
+        conflicts_with_all = ["checkpoint", "store", "low_memory", "typo_model", "transpose_cost", "no_transpositions", "prefix_batching", "stream"]
+    )]
+    gpu: bool,
+
     /// Optionally parallelize the calculations with multiple threads. N=1 means single-threaded.
-    /// Set to N=0 to utilize all-but-one available cores of the running system.
+    /// Set to N=0 to utilize all-but-one available cores of the running system. The printed results do not depend on
+    /// the thread count, pairs of equal distance are always ordered by their line numbers.
     #[arg(short = 'j', long, default_value_t = 1usize)]
     thread_num: usize,
 
+    /// Memory budget in MiB for the pairs waiting to be calculated. With N=0 threads, fewer threads are used if there
+    /// is too little work for all of them.
+    #[arg(long)]
+    max_memory: Option<NonZero<usize>>,
+
+    /// Browse the ranked pairs interactively in the terminal instead of printing them: scroll through them, show
+    /// the diff of a pair, filter them by a threshold (see --threshold) and mark pairs for export.
+    #[cfg(feature = "tui")]
+    #[arg(
+        long,
+        conflicts_with_all = ["low_memory", "best_match", "groups", "outliers", "line_scores", "paired", "queries", "git_base", "stream"]
+    )]
+    tui: bool,
+
+    /// File the pairs marked in the --tui browser are exported to, in the format of --ignore-pairs.
+    #[cfg(feature = "tui")]
+    #[arg(long, value_name = "PATH", default_value = "marked-pairs.txt")]
+    tui_export: PathBuf,
+
+    /// Print the pairs within --threshold (all pairs without one) as soon as they have been calculated instead of
+    /// ranking them, for early results of long runs. Ctrl-C stops scheduling new pairs and prints a summary of the
+    /// pairs calculated so far, pressing it again aborts right away.
+    #[arg(
+        long,
+        conflicts_with_all = ["checkpoint", "store", "low_memory", "prefix_batching", "output", "context", "best_match", "groups", "outliers", "line_scores", "graph", "git_base", "paired", "queries"]
+    )]
+    stream: bool,
+
     /// Also print the two lines between which the distance has been calculated as shown in the end result list.
     #[arg(short = 'p', long)]
     print_lines: bool,
 
-    /// Print additional info
-    #[arg(short = 'v', long)]
-    verbose: bool,
+    /// With --print-lines, also print N lines before and after both lines of every pair of the ranking, with the
+    /// neighbors marked by `-` like by `grep -C`. Only lines of the same input file are shown.
+    #[arg(
+        short = 'C',
+        long,
+        value_name = "N",
+        requires = "print_lines",
+        conflicts_with_all = ["low_memory", "queries"]
+    )]
+    context: Option<NonZero<usize>>,
+
+    /// Also print the byte offset and length of both lines within their input file as `<offset>+<len>`, excluding
+    /// the line ending. With an encoding other than UTF-8 they refer to the input decoded to UTF-8.
+    #[arg(long)]
+    offsets: bool,
+
+    /// Digits after the decimal point of normalized distances.
+    #[arg(long, default_value_t = 4)]
+    precision: usize,
+
+    /// Format of the lines of the ranking, e.g. "{line_a}\t{line_b}\t{dist}\t{norm}". The placeholders {text_a}
+    /// and {text_b} stand for the compared lines and {offset_a}, {len_a}, {offset_b} and {len_b} for their byte
+    /// ranges (see --offsets). --print-lines has no effect on the templated lines.
+    #[arg(long, value_parser = parse_template)]
+    template: Option<String>,
+
+    /// Instead of ranking all pairs, print one row per line with its closest partner, in the order of the lines.
+    #[arg(long)]
+    best_match: bool,
+
+    /// Distance (normalized with --normalize) up to which the lines of a pair are considered similar. With the
+    /// similarity normalization it is the minimum similarity instead, with --as-similarity in percent.
+    #[arg(long, value_name = "D")]
+    threshold: Option<f32>,
+
+    /// Instead of ranking all pairs, merge lines which are transitively similar (see --threshold) into groups and
+    /// print them by size, each with its representative line.
+    #[arg(long, requires = "threshold", conflicts_with = "best_match")]
+    groups: bool,
+
+    /// Instead of ranking all pairs, print the K lines least like all others, i.e. those with the largest distance
+    /// to the lines they have been compared with (the smallest similarity with the similarity normalization).
+    #[arg(long, value_name = "K", conflicts_with_all = ["best_match", "groups"])]
+    outliers: Option<NonZero<usize>>,
+
+    /// Aggregate of the distances of a line by which --outliers ranks it.
+    #[arg(long, value_enum, value_name = "SCORE", default_value_t = OutlierScore::Mean)]
+    outlier_score: OutlierScore,
+
+    /// Instead of ranking all pairs, print every line with the mean of its normalized distances to the lines it has
+    /// been compared with and the one to its closest partner, sorted by the mean (see --descending).
+    #[arg(long, conflicts_with_all = ["best_match", "groups", "outliers"])]
+    line_scores: bool,
+
+    /// Write a graph of the lines connected by their similar pairs (see --threshold) to this file, as GraphML for
+    /// `.graphml` paths and as DOT otherwise.
+    #[arg(long, value_name = "PATH", requires = "threshold")]
+    graph: Option<PathBuf>,
+
+    /// Compare the lines of the input file against those of the same file at the given git revision instead and
+    /// report which lines have been moved, mutated, added or deleted. A line only counts as mutated from an old one if
+    /// at most half of the longer of both has been edited.
+    #[arg(
+        long,
+        value_name = "REV",
+        conflicts_with_all = ["min_len", "max_len", "max_line_len", "ignore_pairs", "timeout", "graph", "format"]
+    )]
+    git_base: Option<String>,
+
+    /// Compare every line of the first input file only with the line at the same position in the second one and
+    /// report the distance of every row along with their total, e.g. for grading OCR output against ground truth.
+    #[arg(
+        long,
+        conflicts_with_all = ["git_base", "low_memory", "best_match", "groups", "outliers", "line_scores", "focus_lines", "min_len", "max_len", "max_line_len", "ignore_pairs", "timeout", "graph", "format"]
+    )]
+    paired: bool,
+
+    /// Compare every line of this file only with all lines of the input files, without the pairs within the input,
+    /// and rank these pairs. For matching a few new entries against a large corpus.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["git_base", "paired", "low_memory", "best_match", "groups", "outliers", "line_scores", "focus_lines", "min_len", "max_len", "max_line_len", "ignore_pairs", "timeout", "graph", "format"]
+    )]
+    queries: Option<PathBuf>,
+
+    /// Read the option defaults from this config file instead of an auto-discovered dldist.toml.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Implementation used for calculating the distance of a pair. The DP matrices logged with -vvv are only
+    /// available with the scalar kernel.
+    #[arg(long, value_enum, default_value_t = Kernel::Auto)]
+    kernel: Kernel,
+
+    /// Calculate all pairs by comparing the sorted unique lines one after another and sharing the DP rows of the
+    /// prefix common with the previous line, which pays off for lines with long common prefixes like sorted
+    /// wordlists or log lines. Only for all pairs with the OSA algorithm, -v logs the measured speedup.
+    #[arg(long, conflicts_with_all = ["checkpoint", "store", "low_memory", "focus_lines", "timeout"])]
+    prefix_batching: bool,
+
+    /// What the distance of a pair measures. The GPU only calculates the OSA distance.
+    #[arg(long, value_enum, default_value_t = Algorithm::Osa)]
+    algorithm: Algorithm,
+
+    /// Weight substitutions of physically adjacent keys of the keyboard layout as half an edit, which ranks typos
+    /// above other differences. Distances are then counted in half edits.
+    #[arg(long, value_enum)]
+    typo_model: Option<TypoModel>,
+
+    /// Cost of swapping two adjacent characters, in multiples of half an edit, e.g. 2 to count a swap as two errors
+    /// or 0.5 as half an error. Distances are then counted in half edits.
+    #[arg(long, value_name = "C", value_parser = parse_half_edits)]
+    transpose_cost: Option<u32>,
+
+    /// Do not consider swaps of adjacent characters, which calculates the plain Levenshtein distance.
+    #[arg(long, conflicts_with = "transpose_cost")]
+    no_transpositions: bool,
+
+    /// Log additional info to stderr: -v for progress details, -vv for every calculated pair and -vvv for the DP
+    /// matrices.
+    #[arg(short = 'v', long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Format of the log messages.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
 }
 
-fn main() {
-    // argument parsing & handling
-    let args = Arguments::parse();
-    *VERBOSE.lock().unwrap() = args.verbose;
+/// Compares every current line against all lines of the file at an older revision and prints the lines which have
+/// been moved, mutated or added, each together with its old counterpart, followed by the deleted old lines.
+fn print_git_base_report(args: &Arguments, rev: &str, lines: &[Vec<u8>], base_lines: &[Vec<u8>]) {
+    println!(
+        "==> Calculating {} Damerau-Levenshtein distances between {} current and {} old lines...",
+        lines.len() * base_lines.len(),
+        lines.len(),
+        base_lines.len()
+    );
+    let start_time = Instant::now();
+    let (compared_lines, compared_base_lines) =
+        (prepare_lines(lines, args), prepare_lines(base_lines, args));
+    let distance_results = calculate_osa_distances(
+        &compared_lines,
+        &compared_base_lines,
+        cross_pairs(lines.len(), base_lines.len()),
+    );
+    println!(
+        "Calculations done within {:.4}s.",
+        start_time.elapsed().as_secs_f32()
+    );
+    let (changes, deleted) = changes::classify_line_changes(
+        &distance_results,
+        &compared_lines,
+        &compared_base_lines,
+        Settings::current().distance.edit_cost_unit(),
+    );
 
-    if args.thread_num == NUM_ALL_THREADS_AVAILBLE {
-        let res = thread::available_parallelism();
-        if res.is_err() {
-            println!(
-                "WARN: Could not determine thread count from running system. Setting thread_num=1."
-            )
+    println!(
+        "==> Printing moved, mutated and added lines compared to revision '{}':",
+        rev
+    );
+    let (mut unchanged_cnt, mut moved_cnt, mut mutated_cnt, mut added_cnt) =
+        (0u64, 0u64, 0u64, 0u64);
+    for (i, change) in changes.iter().enumerate() {
+        let base_line = match change {
+            LineChange::Unchanged => {
+                unchanged_cnt += 1;
+                continue;
+            }
+            LineChange::Moved(dr) => {
+                moved_cnt += 1;
+                println!("Line {: >4} moved from {: >4}", i + 1, dr.line_b + 1);
+                Some(dr.line_b as usize)
+            }
+            LineChange::Mutated(dr) => {
+                mutated_cnt += 1;
+                println!(
+                    "Line {: >4} mutated from {: >4}: {}",
+                    i + 1,
+                    dr.line_b + 1,
+                    format_distance(args, dr)
+                );
+                Some(dr.line_b as usize)
+            }
+            LineChange::Added => {
+                added_cnt += 1;
+                println!("Line {: >4} added", i + 1);
+                None
+            }
+        };
+
+        if args.print_lines {
+            println!("{: >4}> {}", i + 1, String::from_utf8_lossy(&lines[i]));
+            if let Some(lb) = base_line {
+                println!(
+                    "{}:{}> {}",
+                    rev,
+                    lb + 1,
+                    String::from_utf8_lossy(&base_lines[lb])
+                );
+            }
+            println!();
         }
+    }
 
-        // 2!=0 thus unwrap would not panic
-        *THREAD_NUM.lock().unwrap() =
-            res.unwrap_or(NonZero::<usize>::new(1 + 1).unwrap()).get() - 1;
-    } else {
-        *THREAD_NUM.lock().unwrap() = args.thread_num;
+    println!("==> Printing lines deleted since revision '{}':", rev);
+    for &lb in &deleted {
+        println!("Line {: >4} deleted", lb + 1);
+        if args.print_lines {
+            println!(
+                "{}:{}> {}",
+                rev,
+                lb + 1,
+                String::from_utf8_lossy(&base_lines[lb])
+            );
+            println!();
+        }
     }
-    println!("Running with {} threads.", *THREAD_NUM.lock().unwrap());
+    println!(
+        "{} lines unchanged, {} moved, {} mutated, {} added, {} deleted.",
+        unchanged_cnt,
+        moved_cnt,
+        mutated_cnt,
+        added_cnt,
+        deleted.len()
+    );
+}
 
+/// Compares every query line against all input lines and prints the ranking of these pairs.
+fn print_queries_report(
+    args: &Arguments,
+    queries: (&str, &[Vec<u8>], &[LineSpan]),
+    labels: &[String],
+    lines: &[Vec<u8>],
+    spans: &[LineSpan],
+) {
+    let (queries_path, query_lines, query_spans) = queries;
     println!(
-        "==> Reading in '{}'...",
-        match args.input_file.to_str() {
-            Some(s) => s,
-            None => panic!("Failed to build string from PathBuf (input file)!"),
-        }
+        "==> Calculating {} Damerau-Levenshtein distances between {} queries and {} lines...",
+        query_lines.len() * lines.len(),
+        query_lines.len(),
+        lines.len()
     );
-    let lines = match read_lines(args.input_file) {
-        Ok(lns) => lns,
-        Err(error) => panic!("Failed to read in lines from file: {error:?}"),
-    };
-    let lines_cnt = lines.len();
-    if lines_cnt < 2 {
-        println!(
-            "The file has to contain at least two lines! Counted {}.",
-            lines_cnt
+    let start_time = Instant::now();
+    let mut distance_results = calculate_osa_distances(
+        &prepare_lines(query_lines, args),
+        &prepare_lines(lines, args),
+        cross_pairs(query_lines.len(), lines.len()),
+    );
+    println!(
+        "Calculations done within {:.4}s (without sorting).",
+        start_time.elapsed().as_secs_f32()
+    );
+    let results_cnt = distance_results.len() as u64;
+    sort_distance_results(
+        &mut distance_results,
+        args.normalize,
+        args.descending,
+        &Settings::current(),
+    );
+
+    let print_cnt = print_ranking_header(args, results_cnt);
+    for dr in distance_results.iter().take(print_cnt as usize) {
+        let (lq, l) = (dr.line_a as usize, dr.line_b as usize);
+        print_distance_result(
+            args,
+            dr,
+            (&format!("{}:{}", queries_path, lq + 1), &labels[l]),
+            (&query_lines[lq], &lines[l]),
+            (query_spans[lq], spans[l]),
         );
-        return;
     }
+}
 
-    let combinations_cnt = pair_combinations_count(lines_cnt as u32);
+/// Compares every line of the first file only with the line at the same position in the second file and prints the
+/// distances row by row along with their total.
+fn print_paired_report(args: &Arguments, files: (&str, &str), lines: (&[Vec<u8>], &[Vec<u8>])) {
+    let rows_cnt = lines.0.len().min(lines.1.len());
     println!(
-        "==> Calculating {} Damerau-Levenshtein distances between {} lines...",
-        combinations_cnt, lines_cnt
+        "==> Calculating {} Damerau-Levenshtein distances between the rows of '{}' and '{}'...",
+        rows_cnt, files.0, files.1
     );
-    // calculate all distances
     let start_time = Instant::now();
-    let mut distance_results = calculate_osa_distances(&lines);
-    if distance_results.len() as u32 != combinations_cnt {
-        panic!("Somehow the size of the result combinations list ({}) does not equal the theoretical count ({})!?",
-            distance_results.len(),
-            combinations_cnt);
-    }
+    let mut distance_results = calculate_osa_distances(
+        &prepare_lines(lines.0, args),
+        &prepare_lines(lines.1, args),
+        (0..rows_cnt).map(|i| (i, i)),
+    );
     println!(
-        "Calculations done within {:.4}s (without sorting).",
+        "Calculations done within {:.4}s.",
         start_time.elapsed().as_secs_f32()
     );
-    // sort depending on user settings
-    if args.normalize {
-        if args.descending {
-            distance_results.sort_by(|a, b| {
-                b.normalized_dldist
-                    .partial_cmp(&a.normalized_dldist)
-                    .unwrap_or(Ordering::Equal)
-            });
-        } else {
-            distance_results.sort_by(|a, b| {
-                a.normalized_dldist
-                    .partial_cmp(&b.normalized_dldist)
-                    .unwrap_or(Ordering::Equal)
-            });
-        }
-    } else {
-        if args.descending {
-            distance_results.sort_by(|a, b| b.dldist.cmp(&a.dldist));
-        } else {
-            distance_results.sort_by(|a, b| a.dldist.cmp(&b.dldist));
+    // the threads finish the rows in any order
+    distance_results.sort_by_key(|dr| dr.line_a);
+
+    println!("==> Printing the distance of every row:");
+    for dr in &distance_results {
+        let row = dr.line_a as usize;
+        println!("Row {: >4}: {}", row + 1, format_distance(args, dr));
+        if args.print_lines {
+            println!(
+                "{: >4}< {}",
+                row + 1,
+                String::from_utf8_lossy(&lines.0[row])
+            );
+            println!(
+                "{: >4}> {}",
+                row + 1,
+                String::from_utf8_lossy(&lines.1[row])
+            );
+            println!();
         }
     }
 
-    let print_cnt_limit = combinations_cnt.min(args.n_pairs as u32);
+    let total: u64 = distance_results.iter().map(|dr| dr.dldist as u64).sum();
+    let mean = |value: f64| value / rows_cnt.max(1) as f64;
     println!(
-        "==> Printing{} {} results in {} order:",
-        if args.normalize { " normalized" } else { "" },
-        if args.n_pairs == NUM_PRINT_ALL {
-            format!("all {}", combinations_cnt)
-        } else {
-            format!("top {}", print_cnt_limit)
-        },
-        if args.descending {
-            "descending"
+        "Total distance of {} rows: {} (mean {:.*}{}).",
+        rows_cnt,
+        total,
+        args.precision,
+        mean(total as f64),
+        if args.normalize {
+            let normalized: f64 = distance_results
+                .iter()
+                .map(|dr| dr.normalized_dldist as f64)
+                .sum();
+            format!(
+                ", {} {}",
+                if args.as_similarity {
+                    "similarity"
+                } else {
+                    "normalized"
+                },
+                format_normalized(args, mean(normalized) as f32)
+            )
         } else {
-            "ascending"
+            String::new()
         }
     );
-    let print_cnt = if args.n_pairs == NUM_PRINT_ALL {
-        combinations_cnt
-    } else {
-        print_cnt_limit
+    for (file, file_lines) in [(files.0, lines.0), (files.1, lines.1)] {
+        if file_lines.len() > rows_cnt {
+            println!(
+                "WARN: The last {} lines of '{}' have no counterpart.",
+                file_lines.len() - rows_cnt,
+                file
+            );
+        }
+    }
+}
+
+/// Prints the closest partner of every line instead of the global ranking of all pairs.
+fn print_best_match_report(
+    args: &Arguments,
+    labels: &[String],
+    lines: &[Vec<u8>],
+    distance_results: &[DistanceResult],
+) {
+    let similarity = is_similarity();
+    // smaller values are closer, similarities have to be turned around for that
+    let rank_key = |dr: &DistanceResult| {
+        if !args.normalize {
+            dr.dldist as f32
+        } else if similarity {
+            -dr.normalized_dldist
+        } else {
+            dr.normalized_dldist
+        }
     };
-    for i in 0..print_cnt as usize {
-        let dr = &distance_results[i];
-        // print padded values
+    let mut best_matches: Vec<Option<(usize, &DistanceResult)>> = vec![None; lines.len()];
+    for dr in distance_results {
+        for (line, partner) in [(dr.line_a, dr.line_b), (dr.line_b, dr.line_a)] {
+            let best = &mut best_matches[line as usize];
+            // preferring the partner with the lower index on equal distances
+            if best.is_none_or(|(p, b)| (rank_key(dr), partner as usize) < (rank_key(b), p)) {
+                *best = Some((partner as usize, dr));
+            }
+        }
+    }
+
+    println!(
+        "==> Printing the closest partner of every {}:",
+        match args.unit {
+            Unit::Line => "line",
+            Unit::File => "file",
+        }
+    );
+    for (i, best) in best_matches.iter().enumerate() {
+        let unit = match args.unit {
+            Unit::Line => "Line",
+            Unit::File => "File",
+        };
+        let Some((partner, dr)) = best else {
+            println!("{} {: >4} has not been compared", unit, labels[i]);
+            continue;
+        };
         println!(
-            "Line {: >4} vs. {: >4}: {}",
-            dr.line_a + 1,
-            dr.line_b + 1,
+            "{} {: >4} closest to {: >4}: {}",
+            unit,
+            labels[i],
+            labels[*partner],
+            format_distance(args, dr)
+        );
+
+        if args.print_lines {
+            println!("{: >4}> {}", labels[i], String::from_utf8_lossy(&lines[i]));
+            println!(
+                "{: >4}> {}",
+                labels[*partner],
+                String::from_utf8_lossy(&lines[*partner])
+            );
+            println!();
+        }
+    }
+}
+
+/// Prints the groups of transitively similar lines, largest first.
+fn print_groups_report(
+    args: &Arguments,
+    labels: &[String],
+    lines: &[Vec<u8>],
+    distance_results: &[DistanceResult],
+    threshold: f32,
+) {
+    let settings = Settings::current();
+    let similar_pairs = distance_results
+        .iter()
+        .filter(|dr| dr.is_similar(args.normalize, threshold, &settings))
+        .map(|dr| (dr.line_a as usize, dr.line_b as usize));
+    let groups = groups::group_similar_lines(lines.len(), similar_pairs);
+    let unit_name = match args.unit {
+        Unit::Line => "line",
+        Unit::File => "file",
+    };
+
+    println!(
+        "==> Printing {} groups of similar {}s:",
+        groups.len(),
+        unit_name
+    );
+    for (i, group) in groups.iter().enumerate() {
+        println!(
+            "Group {: >4}: {} {}s, represented by {: >4}",
+            i + 1,
+            group.members.len(),
+            unit_name,
+            labels[group.representative]
+        );
+        println!(
+            "{: >4}> {}",
+            labels[group.representative],
+            String::from_utf8_lossy(&lines[group.representative])
+        );
+        if args.print_lines {
+            for &member in group.members.iter().filter(|&&m| m != group.representative) {
+                println!(
+                    "{: >4}> {}",
+                    labels[member],
+                    String::from_utf8_lossy(&lines[member])
+                );
+            }
+        } else {
+            println!(
+                "Members: {}",
+                group
+                    .members
+                    .iter()
+                    .map(|&m| labels[m].as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        println!();
+    }
+    let grouped_cnt: usize = groups.iter().map(|g| g.members.len()).sum();
+    println!(
+        "{} {}s grouped, {} without similar partner.",
+        grouped_cnt,
+        unit_name,
+        lines.len() - grouped_cnt
+    );
+}
+
+/// Prints the lines with the largest distances to the lines they have been compared with, most distant first.
+fn print_outliers_report(
+    args: &Arguments,
+    labels: &[String],
+    lines: &[Vec<u8>],
+    distance_results: &[DistanceResult],
+    k: usize,
+) {
+    let similarity = args.normalize && is_similarity();
+    let scores = scores::line_scores(lines.len(), distance_results, |dr| {
+        if args.normalize {
+            dr.normalized_dldist
+        } else {
+            dr.dldist as f32
+        }
+    });
+    let mut outliers: Vec<(usize, f32)> = scores
+        .iter()
+        .enumerate()
+        .filter_map(|(i, score)| {
+            score.map(|score| match args.outlier_score {
+                OutlierScore::Mean => (i, score.mean),
+                OutlierScore::Min if similarity => (i, score.max),
+                OutlierScore::Min => (i, score.min),
+            })
+        })
+        .collect();
+    // larger distances are less alike, similarities have to be turned around for that
+    outliers.sort_by(|(i_a, a), (i_b, b)| {
+        let order = if similarity {
+            a.total_cmp(b)
+        } else {
+            b.total_cmp(a)
+        };
+        order.then(i_a.cmp(i_b))
+    });
+    outliers.truncate(k);
+
+    let unit_name = match args.unit {
+        Unit::Line => "line",
+        Unit::File => "file",
+    };
+    println!(
+        "==> Printing {} {}s least like the others by their {}{} {}:",
+        outliers.len(),
+        unit_name,
+        match args.outlier_score {
+            OutlierScore::Mean => "mean",
+            OutlierScore::Min => "closest",
+        },
+        if args.normalize { " normalized" } else { "" },
+        if similarity { "similarity" } else { "distance" }
+    );
+    for (i, _) in outliers {
+        print_line_score(args, &labels[i], &lines[i], scores[i], similarity);
+    }
+}
+
+/// Prints the mean of all normalized distances of every line and the one to its closest partner, sorted by the mean.
+fn print_line_scores_report(
+    args: &Arguments,
+    labels: &[String],
+    lines: &[Vec<u8>],
+    distance_results: &[DistanceResult],
+) {
+    let similarity = is_similarity();
+    let scores = scores::line_scores(lines.len(), distance_results, |dr| dr.normalized_dldist);
+    let mut order: Vec<usize> = (0..lines.len()).collect();
+    // lines which have not been compared come last in either direction
+    order.sort_by(|&a, &b| match (scores[a], scores[b]) {
+        (Some(score_a), Some(score_b)) if args.descending => {
+            score_b.mean.total_cmp(&score_a.mean).then(a.cmp(&b))
+        }
+        (Some(score_a), Some(score_b)) => score_a.mean.total_cmp(&score_b.mean).then(a.cmp(&b)),
+        (a_score, b_score) => b_score.is_some().cmp(&a_score.is_some()).then(a.cmp(&b)),
+    });
+
+    println!(
+        "==> Printing the scores of all {} {}s by their mean normalized {} in {} order:",
+        lines.len(),
+        match args.unit {
+            Unit::Line => "line",
+            Unit::File => "file",
+        },
+        if similarity { "similarity" } else { "distance" },
+        if args.descending {
+            "descending"
+        } else {
+            "ascending"
+        }
+    );
+    for i in order {
+        print_line_score(args, &labels[i], &lines[i], scores[i], similarity);
+    }
+}
+
+/// Prints the row of a single line of --outliers and --line-scores, followed by the line if requested.
+fn print_line_score(
+    args: &Arguments,
+    label: &str,
+    line: &[u8],
+    score: Option<scores::LineScore>,
+    similarity: bool,
+) {
+    let unit = match args.unit {
+        Unit::Line => "Line",
+        Unit::File => "File",
+    };
+    match score {
+        Some(score) => println!(
+            "{} {: >4}: mean {}, closest {} over {} partners",
+            unit,
+            label,
+            format_normalized(args, score.mean),
+            format_normalized(args, if similarity { score.max } else { score.min }),
+            score.partners_cnt
+        ),
+        None => println!("{} {: >4} has not been compared", unit, label),
+    }
+    if args.print_lines {
+        println!("{: >4}> {}", label, String::from_utf8_lossy(line));
+        println!();
+    }
+}
+
+/// Writes the distances of all pairs as a matrix into the --output file, or prints it if there is none.
+fn print_matrix_report(
+    args: &Arguments,
+    labels: &[String],
+    lines: &[Vec<u8>],
+    distance_results: &[DistanceResult],
+) {
+    let unit_name = match args.unit {
+        Unit::Line => "line",
+        Unit::File => "file",
+    };
+    let cell = |dr: &DistanceResult| {
+        if args.normalize {
+            format!("{:.*}", normalized_precision(args), dr.normalized_dldist)
+        } else {
+            dr.dldist.to_string()
+        }
+    };
+    let written = match &args.output {
+        Some(out_path) => File::create(out_path).and_then(|file| {
+            matrix::write(
+                &mut io::BufWriter::new(file),
+                distance_results,
+                labels,
+                lines,
+                cell,
+            )
+        }),
+        None => {
+            println!(
+                "==> Printing{} distance matrix of {} {}s:",
+                if args.normalize { " normalized" } else { "" },
+                lines.len(),
+                unit_name
+            );
+            matrix::write(
+                &mut io::stdout().lock(),
+                distance_results,
+                labels,
+                lines,
+                cell,
+            )
+        }
+    };
+    if let Err(error) = written {
+        panic!("Failed to write distance matrix: {error:?}");
+    }
+    if let Some(out_path) = &args.output {
+        println!(
+            "==> Wrote distance matrix of {} {}s into '{}'.",
+            lines.len(),
+            unit_name,
+            out_path.display()
+        );
+    }
+}
+
+/// Prints the lines (or the paths of the files) in their order, leaving out those similar to an earlier one, or
+/// writes them into the --output file.
+fn print_dedupe_report(
+    args: &Arguments,
+    labels: &[String],
+    lines: &[Vec<u8>],
+    distance_results: &[DistanceResult],
+) {
+    let unit_name = match args.unit {
+        Unit::Line => "line",
+        Unit::File => "file",
+    };
+    let settings = Settings::current();
+    // only equal lines are similar by default, which is a distance of 0 or a similarity of 1 (100%)
+    let threshold = args.threshold.unwrap_or_else(|| {
+        if args.normalize {
+            DistanceResult::new(0, 0, 1, 1, 0, &settings).normalized_dldist
+        } else {
+            0.0
+        }
+    });
+    let similar_pairs = distance_results
+        .iter()
+        .filter(|dr| dr.is_similar(args.normalize, threshold, &settings))
+        .map(|dr| (dr.line_a as usize, dr.line_b as usize));
+    // every group is kept as its first member
+    let mut removed = vec![false; lines.len()];
+    for group in groups::group_similar_lines(lines.len(), similar_pairs) {
+        for &member in &group.members[1..] {
+            removed[member] = true;
+        }
+    }
+    let kept: Vec<&[u8]> = (0..lines.len())
+        .filter(|&i| !removed[i])
+        .map(|i| match args.unit {
+            Unit::Line => lines[i].as_slice(),
+            Unit::File => labels[i].as_bytes(),
+        })
+        .collect();
+    let removed_cnt = lines.len() - kept.len();
+
+    if let Some(out_path) = &args.output {
+        let written = File::create(out_path).and_then(|file| {
+            let mut writer = io::BufWriter::new(file);
+            for line in &kept {
+                writer.write_all(line)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()
+        });
+        if let Err(error) = written {
+            panic!("Failed to write deduplicated {}s: {error:?}", unit_name);
+        }
+        println!(
+            "==> Wrote {} of {} {}s into '{}', {} similar ones removed.",
+            kept.len(),
+            lines.len(),
+            unit_name,
+            out_path.display(),
+            removed_cnt
+        );
+        return;
+    }
+    println!(
+        "==> Printing {} of {} {}s, {} similar ones removed:",
+        kept.len(),
+        lines.len(),
+        unit_name,
+        removed_cnt
+    );
+    for line in kept {
+        println!("{}", String::from_utf8_lossy(line));
+    }
+}
+
+/// Prints summary statistics of the distances of the pairs along with a histogram of them.
+fn print_stats_report(
+    args: &Arguments,
+    distance_results: &[DistanceResult],
+    combinations_cnt: u64,
+) {
+    const BAR_WIDTH: usize = 40;
+    let mut distances: Vec<f32> = distance_results
+        .iter()
+        .map(|dr| {
             if args.normalize {
-                format!(
-                    "norm. {:2.4} (dist. {: >3})",
-                    dr.normalized_dldist, dr.dldist
-                )
+                dr.normalized_dldist
             } else {
-                format!("{: >3}", dr.dldist)
+                dr.dldist as f32
             }
+        })
+        .collect();
+    distances.sort_by(f32::total_cmp);
+    let pairs_cnt = distances.len();
+    println!(
+        "==> Printing statistics of the {} of {} of {} pairs:",
+        if args.as_similarity {
+            "similarities"
+        } else if args.normalize {
+            "normalized distances"
+        } else {
+            "distances"
+        },
+        pairs_cnt,
+        combinations_cnt
+    );
+    if pairs_cnt == 0 {
+        return;
+    }
+
+    let mean = distances.iter().map(|&d| d as f64).sum::<f64>() / pairs_cnt as f64;
+    let variance = distances
+        .iter()
+        .map(|&d| (d as f64 - mean).powi(2))
+        .sum::<f64>()
+        / pairs_cnt as f64;
+    // nearest-rank percentiles, which are actual distances of pairs
+    let percentile = |p: usize| distances[(p * pairs_cnt).div_ceil(100).max(1) - 1];
+    let format_value = |d: f32| {
+        if args.normalize {
+            format_normalized(args, d)
+        } else {
+            d.to_string()
+        }
+    };
+    println!("Mean:      {}", format_normalized(args, mean as f32));
+    println!(
+        "Std. dev.: {}",
+        format_normalized(args, variance.sqrt() as f32)
+    );
+    println!("Min:       {}", format_value(distances[0]));
+    println!("25%:       {}", format_value(percentile(25)));
+    println!("Median:    {}", format_value(percentile(50)));
+    println!("75%:       {}", format_value(percentile(75)));
+    println!("90%:       {}", format_value(percentile(90)));
+    println!("Max:       {}", format_value(distances[pairs_cnt - 1]));
+    if let Some(threshold) = args.threshold {
+        let settings = Settings::current();
+        let similar_cnt = distance_results
+            .iter()
+            .filter(|dr| dr.is_similar(args.normalize, threshold, &settings))
+            .count();
+        println!(
+            "Similar:   {} pairs ({:.2}%) within threshold {}",
+            similar_cnt,
+            100f64 * similar_cnt as f64 / pairs_cnt as f64,
+            threshold
         );
+    }
 
-        if args.print_lines {
-            println!("{: >4}> {}", dr.line_a + 1, lines[dr.line_a as usize]);
-            println!("{: >4}> {}", dr.line_b + 1, lines[dr.line_b as usize]);
+    println!();
+    let bins = html::histogram_bins(&distances, !args.normalize);
+    let max_cnt = bins
+        .iter()
+        .map(|&(_, _, cnt)| cnt)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let ranges: Vec<String> = bins
+        .iter()
+        .map(|&(lower, upper, _)| {
+            // distances are counted per value unless they spread too widely
+            if !args.normalize && upper - lower == 1.0 {
+                format!("{}", lower)
+            } else {
+                let precision = if args.normalize {
+                    normalized_precision(args)
+                } else {
+                    1
+                };
+                format!("{:.*} to {:.*}", precision, lower, precision, upper)
+            }
+        })
+        .collect();
+    let range_width = ranges.iter().map(String::len).max().unwrap_or(0);
+    for (range, &(_, _, cnt)) in ranges.iter().zip(&bins) {
+        println!(
+            "{: >width$} | {} {}",
+            range,
+            "#".repeat(cnt * BAR_WIDTH / max_cnt),
+            cnt,
+            width = range_width
+        );
+    }
+}
+
+/// Logs the work saved by --prefix-batching, along with the speedup over calculating the pairs separately, which is
+/// measured on a sample of the pairs.
+fn log_prefix_batching_speedup(
+    lines: &[Vec<u8>],
+    results: &[DistanceResult],
+    stats: &prefix::PrefixStats,
+    elapsed: Duration,
+) {
+    const SAMPLE_PAIRS: usize = 1000;
+    let step = (results.len() / SAMPLE_PAIRS).max(1);
+    let sample_start = Instant::now();
+    let mut sample_cnt = 0;
+    for dr in results.iter().step_by(step) {
+        std::hint::black_box(dldist::osa::calculate_osa_distance(
+            &lines[dr.line_a as usize],
+            &lines[dr.line_b as usize],
+        ));
+        sample_cnt += 1;
+    }
+    let separate_secs = sample_start.elapsed().as_secs_f64() / sample_cnt.max(1) as f64
+        * results.len() as f64
+        / *THREAD_NUM.lock().unwrap() as f64;
+    info!(
+        unique_lines = stats.unique_lines,
+        cells = stats.cells,
+        full_cells = stats.full_cells,
+        cells_saved = format!(
+            "{:.2}%",
+            100f64 * (1f64 - stats.cells as f64 / stats.full_cells.max(1) as f64)
+        ),
+        batched_secs = elapsed.as_secs_f64(),
+        separate_secs,
+        speedup = format!("{:.2}x", separate_secs / elapsed.as_secs_f64().max(1e-9)),
+        "prefix batching done"
+    );
+}
+
+/// Describes the lengths excluded by --min-len and --max-len, e.g. "shorter than 3 or longer than 80".
+fn len_bounds(args: &Arguments) -> String {
+    [
+        args.min_len.map(|len| format!("shorter than {}", len)),
+        args.max_len.map(|len| format!("longer than {}", len)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" or ")
+}
+
+/// Returns the amount of results to print out of all results, or `None` for printing all of them.
+fn print_cnt_limit(args: &Arguments, results_cnt: u64) -> Option<u64> {
+    match args.top_percent {
+        Some(percent) => Some(((results_cnt as f64) * percent / 100f64).ceil() as u64),
+        None if args.n_pairs == NUM_PRINT_ALL => None,
+        None => Some(results_cnt.min(args.n_pairs)),
+    }
+}
+
+/// Prints the headline of the ranking and returns the amount of results to print.
+fn print_ranking_header(args: &Arguments, results_cnt: u64) -> u64 {
+    let print_cnt = print_cnt_limit(args, results_cnt);
+    let selection = match (print_cnt, args.top_percent) {
+        (None, _) => format!("all {}", results_cnt),
+        (Some(cnt), Some(percent)) => format!("top {}% ({})", percent, cnt),
+        (Some(cnt), None) => format!("top {}", cnt),
+    };
+    if args.as_similarity {
+        println!(
+            "==> Printing {} results by similarity, {} similar first:",
+            selection,
+            if args.descending { "least" } else { "most" }
+        );
+    } else {
+        println!(
+            "==> Printing{} {} results in {} order:",
+            if args.normalize { " normalized" } else { "" },
+            selection,
+            if args.descending {
+                "descending"
+            } else {
+                "ascending"
+            }
+        );
+    }
+    print_cnt.unwrap_or(results_cnt)
+}
+
+/// Returns the digits after the decimal point of normalized distances, which shift by two for percentages.
+fn normalized_precision(args: &Arguments) -> usize {
+    if args.as_similarity {
+        args.precision.saturating_sub(2)
+    } else {
+        args.precision
+    }
+}
+
+/// Returns the normalized distance, or the similarity in percent with --as-similarity.
+fn format_normalized(args: &Arguments, value: f32) -> String {
+    if args.as_similarity {
+        format!("{:.*}%", normalized_precision(args), value)
+    } else {
+        format!("{:.*}", args.precision, value)
+    }
+}
+
+/// Prints a single result of the ranking, followed by both of its lines if requested.
+/// Returns the padded distance of the pair, along with the normalized one if requested.
+fn format_distance(args: &Arguments, dr: &DistanceResult) -> String {
+    if args.as_similarity {
+        format!(
+            "{: >7} similar (dist. {: >3})",
+            format_normalized(args, dr.normalized_dldist),
+            dr.dldist
+        )
+    } else if args.normalize {
+        format!(
+            "norm. {:2.*} (dist. {: >3})",
+            args.precision, dr.normalized_dldist, dr.dldist
+        )
+    } else {
+        format!("{: >3}", dr.dldist)
+    }
+}
+
+fn print_distance_result(
+    args: &Arguments,
+    dr: &DistanceResult,
+    labels: (&str, &str),
+    lines: (&[u8], &[u8]),
+    spans: (LineSpan, LineSpan),
+) {
+    if let Some(template) = &args.template {
+        let line = render_template(template, |field| match field {
+            "line_a" => labels.0.to_string(),
+            "line_b" => labels.1.to_string(),
+            "text_a" => String::from_utf8_lossy(lines.0).into_owned(),
+            "text_b" => String::from_utf8_lossy(lines.1).into_owned(),
+            "offset_a" => spans.0.offset.to_string(),
+            "offset_b" => spans.1.offset.to_string(),
+            "len_a" => spans.0.len.to_string(),
+            "len_b" => spans.1.len.to_string(),
+            "dist" => dr.dldist.to_string(),
+            _ => format!("{:.*}", normalized_precision(args), dr.normalized_dldist),
+        });
+        println!("{}", line);
+        return;
+    }
+
+    // print padded values
+    println!(
+        "{} {: >4} vs. {: >4}: {}{}",
+        match args.unit {
+            Unit::Line => "Line",
+            Unit::File => "File",
+        },
+        labels.0,
+        labels.1,
+        format_distance(args, dr),
+        if args.offsets {
+            format!(
+                " (bytes {}+{} and {}+{})",
+                spans.0.offset, spans.0.len, spans.1.offset, spans.1.len
+            )
+        } else {
+            String::new()
+        }
+    );
+
+    // with --context the caller prints the lines along with their neighbors
+    if args.print_lines && args.context.is_none() {
+        println!("{: >4}> {}", labels.0, String::from_utf8_lossy(lines.0));
+        println!("{: >4}> {}", labels.1, String::from_utf8_lossy(lines.1));
+        println!();
+    }
+}
+
+/// Prints the line along with up to `context` lines of the same input file before and after it.
+fn print_line_with_context(
+    labels: &[String],
+    lines: &[Vec<u8>],
+    file_ids: &[usize],
+    line: usize,
+    context: usize,
+) {
+    let same_file = |j: &usize| file_ids[*j] == file_ids[line];
+    let first = (line.saturating_sub(context)..line)
+        .find(same_file)
+        .unwrap_or(line);
+    let last = (line + 1..lines.len().min(line + context + 1))
+        .take_while(same_file)
+        .last()
+        .unwrap_or(line);
+    for j in first..=last {
+        println!(
+            "{: >4}{} {}",
+            labels[j],
+            if j == line { '>' } else { '-' },
+            String::from_utf8_lossy(&lines[j])
+        );
+    }
+}
+
+fn main() {
+    // argument parsing & handling
+    let (mut args, mode): (Arguments, Mode) = config::parse_arguments();
+    match &args.command {
+        Some(Commands::Completions { shell }) => {
+            let mut command = modes::with_modes(Arguments::command());
+            let bin_name = command.get_name().to_string();
+            clap_complete::generate(*shell, &mut command, bin_name, &mut io::stdout());
+            return;
+        }
+        Some(Commands::Bench(bench_args)) => {
+            bench::run(bench_args);
+            return;
+        }
+        Some(Commands::Serve(serve_args)) => {
+            serve::run(serve_args);
+            return;
+        }
+        None => {}
+    }
+    init_logging(args.verbose, args.log_format);
+    if let Some(normalization) = args.normalize_by {
+        args.normalize = true;
+        *NORMALIZATION.lock().unwrap() = normalization;
+    }
+    if args.as_similarity {
+        args.normalize = true;
+        *AS_SIMILARITY.lock().unwrap() = true;
+    }
+
+    if args.thread_num == NUM_ALL_THREADS_AVAILBLE {
+        let res = thread::available_parallelism();
+        if res.is_err() {
+            println!(
+                "WARN: Could not determine thread count from running system. Setting thread_num=1."
+            )
+        }
+
+        // 2!=0 thus unwrap would not panic, single-core systems still get one thread
+        *THREAD_NUM.lock().unwrap() =
+            (res.unwrap_or(NonZero::<usize>::new(1 + 1).unwrap()).get() - 1).max(1);
+    } else {
+        *THREAD_NUM.lock().unwrap() = args.thread_num;
+    }
+    *ALGORITHM.lock().unwrap() = args.algorithm;
+    #[cfg(feature = "gpu")]
+    if args.gpu && args.algorithm != Algorithm::Osa {
+        println!("WARN: The GPU only calculates the OSA distance. Using the CPU instead.");
+        args.gpu = false;
+    }
+    let weighted =
+        args.typo_model.is_some() || args.transpose_cost.is_some() || args.no_transpositions;
+    if weighted {
+        let mut costs = match args.typo_model {
+            Some(typo_model) => typo_model.cost_matrix(),
+            None if args.transpose_cost.is_some() => CostMatrix::uniform(2),
+            None => CostMatrix::uniform(1),
+        };
+        if args.no_transpositions {
+            costs.transposition = u32::MAX;
+        } else if let Some(cost) = args.transpose_cost {
+            // the cost is given in half edits, which is the unit of both matrices above
+            costs.transposition = cost;
+        }
+        *COST_MATRIX.lock().unwrap() = Some(Box::leak(Box::new(costs)));
+    }
+    *KERNEL.lock().unwrap() = match args.kernel {
+        // weighted edits are calculated separately from the kernels
+        _ if weighted => Kernel::Scalar,
+        Kernel::Auto if simd::supported() && !tracing::enabled!(Level::TRACE) => Kernel::Simd,
+        Kernel::Auto => Kernel::Scalar,
+        Kernel::Simd if !simd::supported() => {
+            println!(
+                "WARN: The CPU does not support the SIMD kernel. Using the scalar one instead."
+            );
+            Kernel::Scalar
+        }
+        kernel => kernel,
+    };
+    println!(
+        "Running with {} threads{}.",
+        *THREAD_NUM.lock().unwrap(),
+        if *KERNEL.lock().unwrap() == Kernel::Simd {
+            " using the SIMD kernel"
+        } else {
+            ""
+        }
+    );
+
+    let input_files = match collect_input_files(&args.input_files) {
+        Ok(files) => files,
+        Err(error) => panic!("Failed to collect input files: {error:?}"),
+    };
+    if args.low_memory {
+        if args.unit != Unit::Line || args.encoding != InputEncoding::Utf8 {
+            println!("The low-memory mode only supports comparing lines of UTF-8 input files!");
+            return;
+        }
+        #[cfg(feature = "archive")]
+        if input_files
+            .iter()
+            .any(|file| archive::ArchiveInput::parse(file).is_some())
+        {
+            println!("The low-memory mode does not support archives as input files!");
+            return;
+        }
+        external::run(&args, &input_files);
+        return;
+    }
+    // the members of archives are read in right away, each of them counting as an input file of its own
+    let mut inputs: Vec<(String, Option<Vec<u8>>)> = Vec::new();
+    for input_file in &input_files {
+        let path_str = match input_file.to_str() {
+            Some(s) => s,
+            None => panic!("Failed to build string from PathBuf (input file)!"),
+        };
+        #[cfg(feature = "archive")]
+        if let Some(archive) = archive::ArchiveInput::parse(input_file) {
+            println!("==> Reading in archive '{}'...", path_str);
+            let members = match archive.read_members() {
+                Ok(mbrs) => mbrs,
+                Err(error) => panic!("Failed to read in archive: {error:?}"),
+            };
+            for (name, content) in members {
+                inputs.push((
+                    format!("{}:{}", archive.path.display(), name),
+                    Some(content),
+                ));
+            }
+            continue;
+        }
+        inputs.push((path_str.to_string(), None));
+    }
+
+    // when comparing lines of multiple files the file path is required to tell the lines apart
+    let label_with_path = args.unit == Unit::File || inputs.len() > 1;
+    let mut lines: Vec<Vec<u8>> = Vec::new();
+    let mut labels: Vec<String> = Vec::new();
+    let mut spans: Vec<LineSpan> = Vec::new();
+    let mut file_line_cnts: Vec<usize> = Vec::new();
+    // index of the input file of every line, which keeps --context within the file
+    let mut file_ids: Vec<usize> = Vec::new();
+    for (file_id, (path_str, content)) in inputs.iter_mut().enumerate() {
+        let path_str = path_str.as_str();
+        println!("==> Reading in '{}'...", path_str);
+        match args.unit {
+            Unit::Line => {
+                let read = match content.take() {
+                    Some(cnt) => split_lines(
+                        decode_encoding(cnt, args.encoding).as_slice(),
+                        args.invalid_utf8,
+                    ),
+                    None => read_lines(path_str, args.encoding, args.invalid_utf8),
+                };
+                let (file_lines, file_spans) = match read {
+                    Ok(lns) => lns,
+                    Err(error) => panic!("Failed to read in lines from file: {error:?}"),
+                };
+                spans.extend(file_spans);
+                file_line_cnts.push(file_lines.len());
+                file_ids.extend(std::iter::repeat_n(file_id, file_lines.len()));
+                info!(path = path_str, lines = file_lines.len(), "read input file");
+                for (i, line) in file_lines.into_iter().enumerate() {
+                    labels.push(if label_with_path {
+                        format!("{}:{}", path_str, i + 1)
+                    } else {
+                        format!("{}", i + 1)
+                    });
+                    lines.push(line);
+                }
+            }
+            Unit::File => {
+                let read = match content.take() {
+                    Some(cnt) => Ok(cnt),
+                    None => fs::read(path_str),
+                };
+                let content = match read {
+                    Ok(cnt) => {
+                        spans.push(LineSpan {
+                            offset: 0,
+                            len: cnt.len(),
+                        });
+                        match decode_text(decode_encoding(cnt, args.encoding), args.invalid_utf8) {
+                            Ok(text) => text,
+                            Err(error) => panic!(
+                            "Failed to read in file: not valid UTF-8 ({error}), see --invalid-utf8"
+                        ),
+                        }
+                    }
+                    Err(error) => panic!("Failed to read in file: {error:?}"),
+                };
+                labels.push(path_str.to_string());
+                lines.push(content);
+                file_ids.push(file_id);
+            }
+        }
+    }
+    if let Some(rev) = &args.git_base {
+        if args.unit != Unit::Line || input_files.len() != 1 {
+            println!(
+                "Comparing against a git revision requires a single input file compared by lines!"
+            );
+            return;
+        }
+        println!(
+            "==> Reading in '{}' at revision '{}'...",
+            input_files[0].display(),
+            rev
+        );
+        let base_lines =
+            match read_git_lines(&input_files[0], rev, args.encoding, args.invalid_utf8) {
+                Ok(lns) => lns,
+                Err(error) => panic!("Failed to read in lines from git revision: {error:?}"),
+            };
+        print_git_base_report(&args, rev, &lines, &base_lines);
+        return;
+    }
+    if args.paired {
+        if args.unit != Unit::Line || inputs.len() != 2 {
+            println!("Comparing the rows of files requires two input files compared by lines!");
+            return;
+        }
+        let (lines_a, lines_b) = lines.split_at(file_line_cnts[0]);
+        print_paired_report(&args, (&inputs[0].0, &inputs[1].0), (lines_a, lines_b));
+        return;
+    }
+
+    if let Some(queries_path) = &args.queries {
+        if args.unit != Unit::Line {
+            println!("Comparing against queries requires the input to be compared by lines!");
+            return;
+        }
+        println!("==> Reading in queries '{}'...", queries_path.display());
+        let (query_lines, query_spans) =
+            match read_lines(queries_path, args.encoding, args.invalid_utf8) {
+                Ok(read) => read,
+                Err(error) => panic!("Failed to read in queries: {error:?}"),
+            };
+        print_queries_report(
+            &args,
+            (&queries_path.to_string_lossy(), &query_lines, &query_spans),
+            &labels,
+            &lines,
+            &spans,
+        );
+        return;
+    }
+
+    let unit_name = match args.unit {
+        Unit::Line => "line",
+        Unit::File => "file",
+    };
+    let lines_cnt = lines.len();
+    if lines_cnt < 2 {
+        println!(
+            "The input has to contain at least two {}s! Counted {}.",
+            unit_name, lines_cnt
+        );
+        return;
+    }
+
+    let mut focus_lines: Vec<usize> = args.focus_lines.iter().map(|n| n.get() - 1).collect();
+    focus_lines.sort_unstable();
+    focus_lines.dedup();
+    if let Some(&line) = focus_lines.iter().find(|&&l| l >= lines_cnt) {
+        println!(
+            "The focused {} {} does not exist! Counted {}.",
+            unit_name,
+            line + 1,
+            lines_cnt
+        );
+        return;
+    }
+
+    // lines left out of the comparison, in ascending order
+    let mut skipped_lines: Vec<usize> = Vec::new();
+    if let Some(max_len) = args.max_line_len.map(NonZero::get) {
+        let long_lines: Vec<usize> = (0..lines_cnt)
+            .filter(|&i| lines[i].len() > max_len)
+            .collect();
+        if let (LongLines::Error, Some(&i)) = (args.long_lines, long_lines.first()) {
+            println!(
+                "The {} {} is longer than {} bytes ({} bytes), see --long-lines!",
+                unit_name,
+                labels[i],
+                max_len,
+                lines[i].len()
+            );
+            return;
+        }
+        if !long_lines.is_empty() {
+            println!(
+                "==> {} {}s longer than {} bytes are {}.",
+                long_lines.len(),
+                unit_name,
+                max_len,
+                match args.long_lines {
+                    LongLines::Skip => "skipped",
+                    LongLines::Truncate | LongLines::Error => "truncated",
+                }
+            );
+        }
+        if args.long_lines == LongLines::Skip {
+            skipped_lines = long_lines;
+        }
+    }
+    if args.min_len.is_some() || args.max_len.is_some() {
+        let len_range = args.min_len.unwrap_or(0)..=args.max_len.unwrap_or(usize::MAX);
+        let outside_lines: Vec<usize> = (0..lines_cnt)
+            .filter(|&i| !len_range.contains(&lines[i].len()))
+            .collect();
+        if !outside_lines.is_empty() {
+            println!(
+                "==> {} {}s {} bytes are skipped.",
+                outside_lines.len(),
+                unit_name,
+                len_bounds(&args)
+            );
+        }
+        skipped_lines.extend(outside_lines);
+        skipped_lines.sort_unstable();
+        skipped_lines.dedup();
+    }
+    if !skipped_lines.is_empty() {
+        if let Some(&line) = focus_lines
+            .iter()
+            .find(|l| skipped_lines.binary_search(l).is_ok())
+        {
+            println!("The focused {} {} is skipped!", unit_name, line + 1);
+            return;
+        }
+        // focus lines refer to the original positions, which move by the amount of removed lines before them
+        for line in focus_lines.iter_mut() {
+            *line -= skipped_lines.partition_point(|&l| l < *line);
+        }
+        let mut i = 0;
+        lines.retain(|_| {
+            i += 1;
+            skipped_lines.binary_search(&(i - 1)).is_err()
+        });
+        let mut i = 0;
+        labels.retain(|_| {
+            i += 1;
+            skipped_lines.binary_search(&(i - 1)).is_err()
+        });
+        let mut i = 0;
+        spans.retain(|_| {
+            i += 1;
+            skipped_lines.binary_search(&(i - 1)).is_err()
+        });
+        let mut i = 0;
+        file_ids.retain(|_| {
+            i += 1;
+            skipped_lines.binary_search(&(i - 1)).is_err()
+        });
+    }
+    let lines_cnt = lines.len();
+    if lines_cnt < 2 {
+        println!(
+            "The input has to contain at least two {}s without skipped ones! Counted {}.",
+            unit_name, lines_cnt
+        );
+        return;
+    }
+
+    let window = args.window.map_or(lines_cnt, NonZero::get);
+    let compared_lines = prepare_lines(&lines, &args);
+    let ignore_list = args
+        .ignore_pairs
+        .as_ref()
+        .map(|path| match ignore::IgnoreList::read(path) {
+            Ok(list) => list,
+            Err(error) => panic!("Failed to read in pairs to ignore: {error:?}"),
+        });
+    let is_ignored = |dr: &DistanceResult| {
+        let (la, lb) = (dr.line_a as usize, dr.line_b as usize);
+        ignore_list
+            .as_ref()
+            .is_some_and(|list| list.contains(&labels[la], &labels[lb], &lines[la], &lines[lb]))
+    };
+    let combinations_cnt = if focus_lines.is_empty() {
+        window_combinations_count(lines_cnt as u64, window as u64)
+    } else {
+        focus_pairs(lines_cnt, window, &focus_lines).count() as u64
+    };
+    println!(
+        "==> Calculating {} Damerau-Levenshtein distances between {} {}s...",
+        combinations_cnt, lines_cnt, unit_name
+    );
+    #[cfg(feature = "parquet")]
+    let streamed = args.format == OutputFormat::Parquet;
+    #[cfg(not(feature = "parquet"))]
+    let streamed = false;
+    #[cfg(feature = "sqlite")]
+    let streamed = streamed || args.sqlite.is_some();
+    let max_memory = args.max_memory.map(|mib| mib.get() << 20);
+    if args.thread_num == NUM_ALL_THREADS_AVAILBLE {
+        let max_threads = *THREAD_NUM.lock().unwrap();
+        let thread_num = auto_thread_num(&compared_lines, combinations_cnt, max_threads);
+        if thread_num < max_threads {
+            println!(
+                "==> Using only {} threads for this amount of work.",
+                thread_num
+            );
+            *THREAD_NUM.lock().unwrap() = thread_num;
+        }
+    }
+    let tuning = Tuning::for_lines(&compared_lines, *THREAD_NUM.lock().unwrap(), max_memory);
+    info!(
+        chunk_pairs = tuning.chunk_pairs,
+        max_queued_jobs = tuning.max_queued_jobs,
+        "tuned jobs"
+    );
+    *TUNING.lock().unwrap() = tuning;
+    let results_bytes =
+        combinations_cnt.saturating_mul(std::mem::size_of::<DistanceResult>() as u64);
+    if max_memory.is_some_and(|budget| !streamed && results_bytes > budget as u64) {
+        println!(
+            "WARN: Holding all results requires about {} MiB, more than --max-memory. Consider --low-memory or a smaller --window.",
+            results_bytes >> 20
+        );
+    }
+    if combinations_cnt >= LARGE_PAIR_CNT {
+        print_large_run_estimate(&compared_lines, combinations_cnt, !streamed);
+    }
+    #[cfg(feature = "parquet")]
+    if args.format == OutputFormat::Parquet {
+        // required by clap for this format
+        let out_path = args.output.as_ref().unwrap();
+        let mut sink = match parquet_sink::ParquetSink::create(out_path, &labels) {
+            Ok(snk) => snk,
+            Err(error) => panic!("Failed to create Parquet file: {error:?}"),
+        };
+        let start_time = Instant::now();
+        let deadline = args
+            .timeout
+            .map(|secs| start_time + Duration::from_secs(secs.get()));
+        let (mut evaluated_cnt, mut written_cnt) = (0u64, 0u64);
+        dldist::engine::for_each_osa_distance(
+            &compared_lines,
+            &compared_lines,
+            selected_pairs(lines_cnt, window, &focus_lines)
+                .take_while(|_| deadline.is_none_or(|d| Instant::now() < d)),
+            |dr| {
+                evaluated_cnt += 1;
+                if is_ignored(&dr) {
+                    return;
+                }
+                if let Err(error) = sink.insert(&dr) {
+                    panic!("Failed to write pair into Parquet file: {error:?}");
+                }
+                written_cnt += 1;
+            },
+        );
+        if let Err(error) = sink.finish() {
+            panic!("Failed to finish Parquet file: {error:?}");
+        }
+        println!(
+            "==> Wrote {} pairs into '{}' within {:.4}s.",
+            written_cnt,
+            out_path.display(),
+            start_time.elapsed().as_secs_f32()
+        );
+        if evaluated_cnt < combinations_cnt {
+            print_timeout_warning(evaluated_cnt, combinations_cnt);
+        }
+        return;
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(db_path) = &args.sqlite {
+        let mut sink = match sqlite::SqliteSink::create(db_path, &labels, &lines) {
+            Ok(snk) => snk,
+            Err(error) => panic!("Failed to set up SQLite database: {error:?}"),
+        };
+        let start_time = Instant::now();
+        let deadline = args
+            .timeout
+            .map(|secs| start_time + Duration::from_secs(secs.get()));
+        let (mut evaluated_cnt, mut written_cnt) = (0u64, 0u64);
+        dldist::engine::for_each_osa_distance(
+            &compared_lines,
+            &compared_lines,
+            selected_pairs(lines_cnt, window, &focus_lines)
+                .take_while(|_| deadline.is_none_or(|d| Instant::now() < d)),
+            |dr| {
+                evaluated_cnt += 1;
+                if is_ignored(&dr) {
+                    return;
+                }
+                if let Err(error) = sink.insert(&dr) {
+                    panic!("Failed to insert pair into SQLite database: {error:?}");
+                }
+                written_cnt += 1;
+            },
+        );
+        if let Err(error) = sink.finish() {
+            panic!("Failed to finish SQLite database: {error:?}");
+        }
+        println!(
+            "==> Wrote {} pairs into '{}' within {:.4}s.",
+            written_cnt,
+            db_path.display(),
+            start_time.elapsed().as_secs_f32()
+        );
+        if evaluated_cnt < combinations_cnt {
+            print_timeout_warning(evaluated_cnt, combinations_cnt);
+        }
+        return;
+    }
+
+    if args.stream {
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let interrupted_handler = Arc::clone(&interrupted);
+        if let Err(error) = ctrlc::set_handler(move || {
+            if interrupted_handler.swap(true, AtomicOrdering::Relaxed) {
+                std::process::exit(130);
+            }
+        }) {
+            println!("WARN: Could not handle Ctrl-C ({error}), it aborts without a summary.");
+        }
+        let settings = Settings::current();
+        let start_time = Instant::now();
+        let deadline = args
+            .timeout
+            .map(|secs| start_time + Duration::from_secs(secs.get()));
+        let order = distance_result_order(args.normalize, args.descending, &settings);
+        let (mut evaluated_cnt, mut streamed_cnt) = (0u64, 0u64);
+        let mut best: Option<DistanceResult> = None;
+        println!(
+            "==> Printing{} pairs{} as they are calculated:",
+            if args.normalize { " normalized" } else { "" },
+            match args.threshold {
+                Some(threshold) => format!(" within threshold {}", threshold),
+                None => String::new(),
+            }
+        );
+        dldist::engine::for_each_osa_distance(
+            &compared_lines,
+            &compared_lines,
+            selected_pairs(lines_cnt, window, &focus_lines).take_while(|_| {
+                !interrupted.load(AtomicOrdering::Relaxed)
+                    && deadline.is_none_or(|d| Instant::now() < d)
+            }),
+            |dr| {
+                evaluated_cnt += 1;
+                if is_ignored(&dr)
+                    || args.threshold.is_some_and(|threshold| {
+                        !dr.is_similar(args.normalize, threshold, &settings)
+                    })
+                {
+                    return;
+                }
+                streamed_cnt += 1;
+                let (la, lb) = (dr.line_a as usize, dr.line_b as usize);
+                print_distance_result(
+                    &args,
+                    &dr,
+                    (&labels[la], &labels[lb]),
+                    (&lines[la], &lines[lb]),
+                    (spans[la], spans[lb]),
+                );
+                if best.as_ref().is_none_or(|b| order(&dr, b).is_lt()) {
+                    best = Some(dr);
+                }
+            },
+        );
+        println!(
+            "==> Printed {} of {} calculated pairs within {:.4}s.",
+            streamed_cnt,
+            evaluated_cnt,
+            start_time.elapsed().as_secs_f32()
+        );
+        if evaluated_cnt < combinations_cnt {
+            println!(
+                "WARN: {}, only {:.2}% of pairs evaluated ({} of {}).",
+                if interrupted.load(AtomicOrdering::Relaxed) {
+                    "Interrupted"
+                } else {
+                    "Timed out"
+                },
+                100f64 * evaluated_cnt as f64 / combinations_cnt as f64,
+                evaluated_cnt,
+                combinations_cnt
+            );
+        }
+        if let Some(dr) = best {
+            let (la, lb) = (dr.line_a as usize, dr.line_b as usize);
+            println!(
+                "{} pair: {} vs. {}: {}",
+                if args.descending { "Top" } else { "Closest" },
+                labels[la],
+                labels[lb],
+                format_distance(&args, &dr)
+            );
+        }
+        return;
+    }
+
+    // calculate all distances
+    let start_time = Instant::now();
+    let deadline = args
+        .timeout
+        .map(|secs| start_time + Duration::from_secs(secs.get()));
+    let scheduled_pairs = || {
+        selected_pairs(lines_cnt, window, &focus_lines)
+            .take_while(move |_| deadline.is_none_or(|d| Instant::now() < d))
+    };
+    // the distances only depend on the compared contents, which are hashed, and on these settings
+    let store_settings = format!(
+        "algorithm={} typo-model={} transpose-cost={}",
+        args.algorithm.to_possible_value().unwrap().get_name(),
+        args.typo_model
+            .and_then(|m| m.to_possible_value())
+            .map_or_else(|| "none".to_string(), |v| v.get_name().to_string()),
+        match (args.no_transpositions, args.transpose_cost) {
+            (true, _) => "none".to_string(),
+            (false, Some(cost)) => (cost as f32 / 2.0).to_string(),
+            (false, None) => "1".to_string(),
+        }
+    );
+    let prefix_batching = args.prefix_batching
+        && if window < lines_cnt || args.algorithm != Algorithm::Osa {
+            println!("WARN: Prefix batching only calculates all pairs with the OSA algorithm. Calculating the pairs separately instead.");
+            false
+        } else {
+            true
+        };
+    let mut distance_results = match &args.checkpoint {
+        Some(checkpoint_path) => {
+            let fingerprint = checkpoint::input_fingerprint(
+                &compared_lines,
+                window,
+                &focus_lines,
+                args.algorithm,
+                args.typo_model,
+                args.no_transpositions
+                    .then_some(u32::MAX)
+                    .or(args.transpose_cost),
+            );
+            let done_results = if args.resume && checkpoint_path.exists() {
+                match checkpoint::read(checkpoint_path, fingerprint, &compared_lines) {
+                    Ok(results) => results,
+                    Err(error) => panic!("Failed to resume from checkpoint: {error:?}"),
+                }
+            } else {
+                Vec::new()
+            };
+            if !done_results.is_empty() {
+                println!(
+                    "==> Resuming from checkpoint with {} of {} pairs already done...",
+                    done_results.len(),
+                    combinations_cnt
+                );
+            }
+            match checkpoint::calculate_osa_distances(
+                checkpoint_path,
+                fingerprint,
+                &compared_lines,
+                scheduled_pairs(),
+                done_results,
+            ) {
+                Ok(results) => results,
+                Err(error) => panic!("Failed to write checkpoint: {error:?}"),
+            }
+        }
+        None if prefix_batching => {
+            let (results, stats) = prefix::calculate_all_osa_distances(&compared_lines);
+            if tracing::enabled!(Level::INFO) {
+                log_prefix_batching_speedup(
+                    &compared_lines,
+                    &results,
+                    &stats,
+                    start_time.elapsed(),
+                );
+            }
+            results
+        }
+        None if args.store.is_some() => {
+            let store_path = args.store.as_ref().unwrap();
+            let store = if store_path.exists() {
+                match store::Store::read(store_path, &store_settings) {
+                    Ok(store) => store,
+                    Err(error) => panic!("Failed to read in store: {error:?}"),
+                }
+            } else {
+                store::Store::default()
+            };
+            let (results, reused_cnt) =
+                store.calculate_osa_distances(&compared_lines, scheduled_pairs());
+            println!(
+                "==> Took {} of {} pairs from the store, calculated {}.",
+                reused_cnt,
+                results.len(),
+                results.len() - reused_cnt
+            );
+            if let Err(error) = store::write(store_path, &store_settings, &compared_lines, &results)
+            {
+                panic!("Failed to write store: {error:?}");
+            }
+            results
+        }
+        #[cfg(feature = "gpu")]
+        None if args.gpu => {
+            match gpu::calculate_osa_distances(&compared_lines, scheduled_pairs()) {
+                Ok(results) => results,
+                Err(error) => {
+                    println!(
+                        "WARN: Could not calculate on the GPU: {error}. Falling back to the CPU."
+                    );
+                    calculate_osa_distances(&compared_lines, &compared_lines, scheduled_pairs())
+                }
+            }
+        }
+        None => calculate_osa_distances(&compared_lines, &compared_lines, scheduled_pairs()),
+    };
+    let evaluated_cnt = distance_results.len() as u64;
+    let timed_out = args.timeout.is_some() && evaluated_cnt < combinations_cnt;
+    if evaluated_cnt != combinations_cnt && !timed_out {
+        panic!("Somehow the size of the result combinations list ({}) does not equal the theoretical count ({})!?",
+            distance_results.len(),
+            combinations_cnt);
+    }
+    println!(
+        "Calculations done within {:.4}s (without sorting).",
+        start_time.elapsed().as_secs_f32()
+    );
+    if timed_out {
+        print_timeout_warning(evaluated_cnt, combinations_cnt);
+    }
+    if ignore_list.is_some() {
+        distance_results.retain(|dr| !is_ignored(dr));
+        println!(
+            "==> Ignored {} listed pairs.",
+            evaluated_cnt - distance_results.len() as u64
+        );
+    }
+    if let (Some(graph_path), Some(threshold)) = (&args.graph, args.threshold) {
+        let settings = Settings::current();
+        let mut edges: Vec<&DistanceResult> = distance_results
+            .iter()
+            .filter(|dr| dr.is_similar(args.normalize, threshold, &settings))
+            .collect();
+        // the results are still in the order the threads finished them
+        edges.sort_by_key(|dr| (dr.line_a, dr.line_b));
+        match graph::write(graph_path, &edges, &labels, &lines) {
+            Ok(nodes_cnt) => println!(
+                "==> Wrote graph with {} nodes and {} edges into '{}'.",
+                nodes_cnt,
+                edges.len(),
+                graph_path.display()
+            ),
+            Err(error) => panic!("Failed to write graph: {error:?}"),
+        }
+    }
+    match mode {
+        Mode::Matrix => {
+            print_matrix_report(&args, &labels, &compared_lines, &distance_results);
+            return;
+        }
+        Mode::Dedupe => {
+            print_dedupe_report(&args, &labels, &lines, &distance_results);
+            return;
+        }
+        Mode::Cluster => {
+            // required by clap for this mode
+            let threshold = args.threshold.unwrap();
+            print_groups_report(&args, &labels, &lines, &distance_results, threshold);
+            return;
+        }
+        Mode::Stats => {
+            print_stats_report(&args, &distance_results, combinations_cnt);
+            return;
+        }
+        Mode::Pairs | Mode::Query => {}
+    }
+    if args.best_match {
+        print_best_match_report(&args, &labels, &lines, &distance_results);
+        return;
+    }
+    if let Some(threshold) = args.threshold.filter(|_| args.groups) {
+        print_groups_report(&args, &labels, &lines, &distance_results, threshold);
+        return;
+    }
+    if let Some(k) = args.outliers {
+        print_outliers_report(&args, &labels, &lines, &distance_results, k.get());
+        return;
+    }
+    if args.line_scores {
+        print_line_scores_report(&args, &labels, &lines, &distance_results);
+        return;
+    }
+    let results_cnt = distance_results.len() as u64;
+    // sort depending on user settings
+    sort_distance_results(
+        &mut distance_results,
+        args.normalize,
+        args.descending,
+        &Settings::current(),
+    );
+
+    if args.format == OutputFormat::Html {
+        // required by clap for this format
+        let out_path = args.output.as_ref().unwrap();
+        let rows_cnt = print_cnt_limit(&args, results_cnt).unwrap_or(results_cnt) as usize;
+        let title = inputs
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let options = html::Options {
+            title: &title,
+            normalize: args.normalize,
+            as_similarity: args.as_similarity,
+            precision: normalized_precision(&args),
+            rows_cnt,
+        };
+        if let Err(error) = html::write(out_path, &distance_results, &labels, &lines, options) {
+            panic!("Failed to write HTML report: {error:?}");
+        }
+        println!(
+            "==> Wrote report of {} of {} pairs into '{}'.",
+            rows_cnt.min(distance_results.len()),
+            results_cnt,
+            out_path.display()
+        );
+        return;
+    }
+    #[cfg(feature = "tui")]
+    if args.tui {
+        let options = tui::Options {
+            normalize: args.normalize,
+            as_similarity: args.as_similarity,
+            precision: normalized_precision(&args),
+            threshold: args.threshold,
+            export_path: &args.tui_export,
+        };
+        if let Err(error) = tui::run(&distance_results, &labels, &lines, options) {
+            panic!("Failed to run the TUI: {error:?}");
+        }
+        return;
+    }
+    let print_cnt = print_ranking_header(&args, results_cnt);
+    for dr in distance_results.iter().take(print_cnt as usize) {
+        let (la, lb) = (dr.line_a as usize, dr.line_b as usize);
+        print_distance_result(
+            &args,
+            dr,
+            (&labels[la], &labels[lb]),
+            (&lines[la], &lines[lb]),
+            (spans[la], spans[lb]),
+        );
+        if let Some(context) = args.context.filter(|_| args.template.is_none()) {
+            print_line_with_context(&labels, &lines, &file_ids, la, context.get());
+            println!("--");
+            print_line_with_context(&labels, &lines, &file_ids, lb, context.get());
             println!();
         }
     }
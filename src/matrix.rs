@@ -0,0 +1,58 @@
+//! Export of the distances of all pairs as a CSV matrix, e.g. for loading them into a spreadsheet or a clustering
+//! library expecting a precomputed distance matrix.
+//!
+//! The first row and column hold the labels of the lines, every other cell the distance of the pair of its row and
+//! column. The matrix is symmetric, its diagonal holds the distance of every line to itself.
+
+use std::io::{self, Write};
+
+use dldist::engine::{DistanceResult, Settings};
+
+fn escape_csv(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Writes the matrix of the results of all pairs of the lines, with the cells formatted by `cell`.
+pub fn write<W: Write>(
+    writer: &mut W,
+    results: &[DistanceResult],
+    labels: &[String],
+    lines: &[Vec<u8>],
+    cell: impl Fn(&DistanceResult) -> String,
+) -> io::Result<()> {
+    let lines_cnt = lines.len();
+    // index of the result of every cell, the cells of the diagonal have none
+    let mut cells = vec![usize::MAX; lines_cnt * lines_cnt];
+    for (k, dr) in results.iter().enumerate() {
+        let (la, lb) = (dr.line_a as usize, dr.line_b as usize);
+        cells[la * lines_cnt + lb] = k;
+        cells[lb * lines_cnt + la] = k;
+    }
+
+    let settings = Settings::current();
+    let labels: Vec<String> = labels.iter().map(|label| escape_csv(label)).collect();
+    writeln!(writer, ",{}", labels.join(","))?;
+    for (i, label) in labels.iter().enumerate() {
+        write!(writer, "{}", label)?;
+        for j in 0..lines_cnt {
+            let value = match cells[i * lines_cnt + j] {
+                usize::MAX => cell(&DistanceResult::new(
+                    i as u64,
+                    i as u64,
+                    lines[i].len(),
+                    lines[i].len(),
+                    0,
+                    &settings,
+                )),
+                k => cell(&results[k]),
+            };
+            write!(writer, ",{}", value)?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
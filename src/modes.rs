@@ -0,0 +1,199 @@
+//! Subcommands for the different analyses of the compared lines, e.g. `dldist-file cluster --threshold 3 app.log`.
+//!
+//! The subcommands are derived from the options of the bare invocation, which stays an alias for `pairs`. Every other
+//! subcommand only takes the options applying to it: the remaining ones are hidden from its help and rejected when
+//! given, instead of being silently ignored.
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command};
+
+/// Analysis selected by the subcommand.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Pairs,
+    Matrix,
+    Dedupe,
+    Cluster,
+    Query,
+    Stats,
+}
+
+/// Options of every mode, for reading in and comparing the lines.
+const COMMON_ARGS: &[&str] = &[
+    "input_files",
+    "unit",
+    "encoding",
+    "invalid_utf8",
+    "unicode_normalize",
+    "phonetic",
+    "numbers",
+    "normalize",
+    "normalize_by",
+    "as_similarity",
+    "algorithm",
+    "typo_model",
+    "transpose_cost",
+    "no_transpositions",
+    "kernel",
+    "thread_num",
+    "max_memory",
+    "config",
+    "verbose",
+    "log_format",
+];
+
+/// Options of every mode but `query`, which compares the lines regardless of their lengths.
+const LENGTH_ARGS: &[&str] = &["max_line_len", "long_lines", "min_len", "max_len"];
+
+impl Mode {
+    pub const ALL: [Mode; 6] = [
+        Mode::Pairs,
+        Mode::Matrix,
+        Mode::Dedupe,
+        Mode::Cluster,
+        Mode::Query,
+        Mode::Stats,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Pairs => "pairs",
+            Mode::Matrix => "matrix",
+            Mode::Dedupe => "dedupe",
+            Mode::Cluster => "cluster",
+            Mode::Query => "query",
+            Mode::Stats => "stats",
+        }
+    }
+
+    fn about(self) -> &'static str {
+        match self {
+            Mode::Pairs => "Rank the pairs of lines by their distance, the same as without a subcommand",
+            Mode::Matrix => "Print the distances of all pairs as a CSV matrix with a row and a column per line",
+            Mode::Dedupe => "Print the lines without those similar to an earlier one, by default exact duplicates",
+            Mode::Cluster => "Merge transitively similar lines into groups and print them by size",
+            Mode::Query => "Rank the pairs of the lines of a --queries file with the lines of the input files",
+            Mode::Stats => "Print summary statistics and a histogram of the distances of the pairs",
+        }
+    }
+
+    /// Options applying to the mode besides the common ones.
+    fn own_args(self) -> &'static [&'static str] {
+        match self {
+            // all options, as for the bare invocation
+            Mode::Pairs => &[],
+            Mode::Matrix => &["precision", "output", "store", "gpu", "prefix_batching"],
+            Mode::Dedupe => &[
+                "threshold",
+                "output",
+                "window",
+                "ignore_pairs",
+                "checkpoint",
+                "resume",
+                "store",
+                "gpu",
+                "prefix_batching",
+            ],
+            Mode::Cluster => &[
+                "threshold",
+                "graph",
+                "print_lines",
+                "window",
+                "ignore_pairs",
+                "checkpoint",
+                "resume",
+                "store",
+                "gpu",
+                "prefix_batching",
+            ],
+            Mode::Query => &[
+                "queries",
+                "n_pairs",
+                "top_percent",
+                "descending",
+                "print_lines",
+                "offsets",
+                "precision",
+                "template",
+            ],
+            Mode::Stats => &[
+                "threshold",
+                "precision",
+                "window",
+                "focus_lines",
+                "ignore_pairs",
+                "checkpoint",
+                "resume",
+                "store",
+                "timeout",
+                "gpu",
+                "prefix_batching",
+            ],
+        }
+    }
+
+    /// Returns whether the option with the given id applies to the mode.
+    pub fn applies(self, id: &str) -> bool {
+        self == Mode::Pairs
+            || COMMON_ARGS.contains(&id)
+            || (self != Mode::Query && LENGTH_ARGS.contains(&id))
+            || self.own_args().contains(&id)
+    }
+
+    pub fn from_name(name: &str) -> Option<Mode> {
+        Mode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+/// Adds a subcommand per mode to the command of the bare invocation, each with a copy of its options.
+pub fn with_modes(command: Command) -> Command {
+    let args: Vec<_> = command.get_arguments().cloned().collect();
+    Mode::ALL.into_iter().fold(command, |command, mode| {
+        // the other options are kept hidden, so that the matches can be read into the same arguments
+        let subcommand = Command::new(mode.name())
+            .about(mode.about())
+            .args(args.iter().map(|arg| {
+                arg.clone()
+                    .hide(arg.is_hide_set() || !mode.applies(arg.get_id().as_str()))
+            }));
+        command.subcommand(match mode {
+            Mode::Query => subcommand.mut_arg("queries", |arg| arg.required(true)),
+            Mode::Cluster => subcommand.mut_arg("threshold", |arg| arg.required(true)),
+            Mode::Dedupe => subcommand.long_about(
+                    "Print the lines in their order without those similar to an earlier one (see --threshold), by \
+                     default exact duplicates. Lines left out of the comparison (see --min-len) are left out as well.",
+                ),
+            _ => subcommand,
+        })
+    })
+}
+
+/// Exits with an error if an option not applying to the mode has been given.
+pub fn reject_inapplicable_args(command: &mut Command, mode: Mode, matches: &ArgMatches) {
+    let given = matches
+        .ids()
+        .map(|id| id.as_str())
+        .filter(|id| !mode.applies(id))
+        .find(|id| matches.value_source(id) == Some(ValueSource::CommandLine));
+    if let Some(id) = given {
+        // building sets the usage of the subcommand up with the name of the binary
+        command.build();
+        let subcommand = command.find_subcommand_mut(mode.name()).unwrap();
+        let long = subcommand
+            .get_arguments()
+            .find(|arg| arg.get_id() == id)
+            .and_then(|arg| arg.get_long())
+            .unwrap_or(id)
+            .to_string();
+        subcommand
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!(
+                    "the argument '--{}' does not apply to '{}'",
+                    long,
+                    mode.name()
+                ),
+            )
+            .exit();
+    }
+}
@@ -0,0 +1,541 @@
+//! Distance kernels calculating the optimal string alignment distance of a single pair.
+//!
+//! The globals hold the selected algorithm, kernel and edit costs. Calculations of many pairs resolve them into
+//! [`DistanceSettings`] once and pass them to every pair, so that the pairs do not contend for the locks and
+//! embedders can calculate with their own settings concurrently.
+
+use std::cmp::Ordering;
+use std::sync::Mutex;
+
+use clap::ValueEnum;
+use tracing::{trace, Level};
+
+use crate::simd;
+
+pub static ALGORITHM: Mutex<Algorithm> = Mutex::new(Algorithm::Osa);
+pub static KERNEL: Mutex<Kernel> = Mutex::new(Kernel::Scalar);
+/// Costs of the edits if they are weighted, which always takes the weighted scalar kernel.
+pub static COST_MATRIX: Mutex<Option<&'static CostMatrix>> = Mutex::new(None);
+
+/// What the distance of a pair measures.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Algorithm {
+    /// Optimal string alignment distance of the whole lines.
+    Osa,
+    /// Distance of the sorted and deduplicated tokens, separated by whitespace, commas, semicolons or pipes, which
+    /// ignores the order of the tokens (like the token set ratio of fuzzywuzzy).
+    TokenSet,
+}
+
+/// Implementation of the distance calculation of a single pair.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Kernel {
+    /// SIMD if supported by the CPU, scalar otherwise.
+    Auto,
+    /// Plain dynamic programming over the full matrix.
+    Scalar,
+    /// Anti-diagonal implementation auto-vectorized for AVX2 or SSE4.1.
+    Simd,
+}
+
+/// Settings of the distance calculation of a pair.
+#[derive(Clone, Copy)]
+pub struct DistanceSettings {
+    pub algorithm: Algorithm,
+    /// Kernel of unweighted distances, `Auto` takes the scalar one.
+    pub kernel: Kernel,
+    /// Costs of the edits if they are weighted, which always takes the weighted scalar kernel.
+    pub costs: Option<&'static CostMatrix>,
+}
+
+impl DistanceSettings {
+    /// Returns the settings as currently set by `ALGORITHM`, `KERNEL` and `COST_MATRIX`.
+    pub fn current() -> Self {
+        DistanceSettings {
+            algorithm: *ALGORITHM.lock().unwrap(),
+            kernel: *KERNEL.lock().unwrap(),
+            costs: *COST_MATRIX.lock().unwrap(),
+        }
+    }
+
+    /// Returns the amount of distance units a single edit counts, which is more than one if the edits are weighted.
+    pub fn edit_cost_unit(&self) -> u32 {
+        self.costs.map_or(1, |costs| costs.unit)
+    }
+}
+
+impl Default for DistanceSettings {
+    /// Unweighted OSA distance with the SIMD kernel if the CPU supports it.
+    fn default() -> Self {
+        DistanceSettings {
+            algorithm: Algorithm::Osa,
+            kernel: if simd::supported() {
+                Kernel::Simd
+            } else {
+                Kernel::Scalar
+            },
+            costs: None,
+        }
+    }
+}
+
+/// Weighted costs of the edits, all of them given in multiples of `1 / unit` edits, so that distances stay integers.
+pub struct CostMatrix {
+    /// Cost of an insertion or a deletion, which is a single edit.
+    pub unit: u32,
+    /// Cost of swapping two adjacent bytes, `u32::MAX` if they can not be swapped.
+    pub transposition: u32,
+    /// Costs of substituting byte `a` with byte `b` at index `a * 256 + b`.
+    substitutions: Vec<u32>,
+}
+
+impl CostMatrix {
+    /// Returns the matrix where every edit costs the same, as in the unweighted distance.
+    pub fn uniform(unit: u32) -> Self {
+        let mut substitutions = vec![unit; 256 * 256];
+        for c in 0..256 {
+            substitutions[c * 256 + c] = 0;
+        }
+        CostMatrix {
+            unit,
+            transposition: unit,
+            substitutions,
+        }
+    }
+
+    pub fn substitution(&self, a: u8, b: u8) -> u32 {
+        self.substitutions[a as usize * 256 + b as usize]
+    }
+
+    pub fn set_substitution(&mut self, a: u8, b: u8, cost: u32) {
+        self.substitutions[a as usize * 256 + b as usize] = cost;
+        self.substitutions[b as usize * 256 + a as usize] = cost;
+    }
+}
+
+/// Keyboard layout whose physically adjacent keys are likely mistyped for each other.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum TypoModel {
+    /// US QWERTY layout.
+    Qwerty,
+    /// French AZERTY layout, with the digits of the top row.
+    Azerty,
+    /// US Dvorak layout.
+    Dvorak,
+}
+
+impl TypoModel {
+    /// Rows of the (unshifted) keys, from the top row downwards. Each row is shifted to the right by less than a key
+    /// compared to the row above.
+    fn rows(self) -> [&'static [u8]; 4] {
+        match self {
+            TypoModel::Qwerty => [
+                b"1234567890-=",
+                b"qwertyuiop[]",
+                b"asdfghjkl;'",
+                b"zxcvbnm,./",
+            ],
+            TypoModel::Azerty => [
+                b"1234567890)=",
+                b"azertyuiop^$",
+                b"qsdfghjklm",
+                b"wxcvbn,;:!",
+            ],
+            TypoModel::Dvorak => [
+                b"1234567890[]",
+                b"',.pyfgcrl/=",
+                b"aoeuidhtns-",
+                b";qjkxbmwvz",
+            ],
+        }
+    }
+
+    /// Returns the costs where substituting adjacent keys (regardless of the case of letters) costs half an edit.
+    pub fn cost_matrix(self) -> CostMatrix {
+        let mut costs = CostMatrix::uniform(2);
+        let rows = self.rows();
+        for (r, row) in rows.iter().enumerate() {
+            for (c, &key) in row.iter().enumerate() {
+                // the key to the right and the two keys touching it in the row below
+                let mut neighbors = vec![row.get(c + 1)];
+                if let Some(below) = rows.get(r + 1) {
+                    neighbors.push(c.checked_sub(1).and_then(|c| below.get(c)));
+                    neighbors.push(below.get(c));
+                }
+                for &neighbor in neighbors.into_iter().flatten() {
+                    for a in [key, key.to_ascii_uppercase()] {
+                        for b in [neighbor, neighbor.to_ascii_uppercase()] {
+                            costs.set_substitution(a, b, 1);
+                        }
+                    }
+                }
+            }
+        }
+        costs
+    }
+}
+
+/// Returns the strings without their longest common prefix and suffix.
+pub fn strip_common_affixes<'a>(str_a: &'a [u8], str_b: &'a [u8]) -> (&'a [u8], &'a [u8]) {
+    let prefix_len = str_a.iter().zip(str_b).take_while(|(a, b)| a == b).count();
+    let (str_a, str_b) = (&str_a[prefix_len..], &str_b[prefix_len..]);
+    let suffix_len = str_a
+        .iter()
+        .rev()
+        .zip(str_b.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (
+        &str_a[..str_a.len() - suffix_len],
+        &str_b[..str_b.len() - suffix_len],
+    )
+}
+
+/// Calculates the distance of the pair with the selected algorithm and kernel.
+pub fn calculate_osa_distance(str_a: &[u8], str_b: &[u8]) -> u32 {
+    calculate_distance_with(str_a, str_b, &DistanceSettings::current())
+}
+
+/// Calculates the distance of the pair like [`calculate_osa_distance`], but with the given settings instead of the
+/// globals.
+pub fn calculate_distance_with(str_a: &[u8], str_b: &[u8], settings: &DistanceSettings) -> u32 {
+    match settings.algorithm {
+        Algorithm::Osa => calculate_edit_distance(str_a, str_b, settings),
+        Algorithm::TokenSet => calculate_token_set_distance(str_a, str_b, settings),
+    }
+}
+
+/// Field separators splitting tokens besides whitespace.
+const TOKEN_SEPARATORS: &[u8] = b",;|";
+
+fn sorted_tokens(line: &[u8]) -> Vec<&[u8]> {
+    let mut tokens: Vec<&[u8]> = line
+        .split(|&c| c.is_ascii_whitespace() || TOKEN_SEPARATORS.contains(&c))
+        .filter(|token| !token.is_empty())
+        .collect();
+    tokens.sort_unstable();
+    tokens.dedup();
+    tokens
+}
+
+/// Calculates the smallest distance between the joined tokens both lines have in common and the common tokens
+/// followed by the remaining tokens of either line, as well as between both of the latter. Lines with the same set of
+/// tokens thus have a distance of 0 regardless of their order, as do lines whose tokens are all contained in the other
+/// line.
+pub fn calculate_token_set_distance(
+    str_a: &[u8],
+    str_b: &[u8],
+    settings: &DistanceSettings,
+) -> u32 {
+    let (tokens_a, tokens_b) = (sorted_tokens(str_a), sorted_tokens(str_b));
+    let (mut common, mut rest_a, mut rest_b) = (Vec::new(), Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    loop {
+        match (tokens_a.get(i), tokens_b.get(j)) {
+            (Some(&a), Some(&b)) => match a.cmp(b) {
+                Ordering::Equal => {
+                    common.push(a);
+                    i += 1;
+                    j += 1;
+                }
+                Ordering::Less => {
+                    rest_a.push(a);
+                    i += 1;
+                }
+                Ordering::Greater => {
+                    rest_b.push(b);
+                    j += 1;
+                }
+            },
+            (Some(&a), None) => {
+                rest_a.push(a);
+                i += 1;
+            }
+            (None, Some(&b)) => {
+                rest_b.push(b);
+                j += 1;
+            }
+            (None, None) => break,
+        }
+    }
+
+    let common = common.join(&b' ');
+    let extend = |rest: Vec<&[u8]>| {
+        let mut joined = common.clone();
+        if !joined.is_empty() && !rest.is_empty() {
+            joined.push(b' ');
+        }
+        joined.extend_from_slice(&rest.join(&b' '));
+        joined
+    };
+    let (combined_a, combined_b) = (extend(rest_a), extend(rest_b));
+    let distance = calculate_edit_distance(&combined_a, &combined_b, settings);
+    if common.is_empty() {
+        // nothing in common is no match at all, rather than as close as the shorter line is short
+        return distance;
+    }
+    distance
+        .min(calculate_edit_distance(&common, &combined_a, settings))
+        .min(calculate_edit_distance(&common, &combined_b, settings))
+}
+
+/// Calculates the edit distance of the pair with the selected kernel. Common prefixes and suffixes do not contribute
+/// to the distance, thus only the differing core of both strings is passed to the kernel.
+fn calculate_edit_distance(str_a: &[u8], str_b: &[u8], settings: &DistanceSettings) -> u32 {
+    let (str_a, str_b) = strip_common_affixes(str_a, str_b);
+    if let Some(costs) = settings.costs {
+        return calculate_weighted_osa_distance(str_a, str_b, costs);
+    }
+    match settings.kernel {
+        Kernel::Simd => simd::calculate_osa_distance(str_a, str_b),
+        Kernel::Auto | Kernel::Scalar => calculate_osa_distance_between_two_strings(str_a, str_b),
+    }
+}
+
+// implementation inspired from: https://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance#Optimal_string_alignment_distance
+pub fn calculate_osa_distance_between_two_strings(str_a: &[u8], str_b: &[u8]) -> u32 {
+    let mut dist = vec![vec![0u32; str_b.len() + 1]; str_a.len() + 1]; // making sure indexing is in correct order
+
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i as u32;
+    }
+    dist[0] = (0..=str_b.len() as u32).collect();
+
+    // using bytes instead of chars since we can not be sure of only UTF-8 characters being included in the file
+    let mut a_prior: u8 = 0x00; // actual initial value does not matter
+    let mut b_prior: u8 = 0x00; // actual initial value does not matter
+    for (i, &a) in str_a.iter().enumerate() {
+        for (j, &b) in str_b.iter().enumerate() {
+            let cost: u32 = if a == b { 0 } else { 1 };
+            dist[i + 1][j + 1] = (dist[i][j + 1] + 1) // deletion
+                .min(dist[i + 1][j] + 1) // insertion
+                .min(dist[i][j] + cost); // substitution
+
+            if i > 0 && j > 0 && a == b_prior && a_prior == b {
+                // transposition
+                dist[i + 1][j + 1] = dist[i + 1][j + 1].min(dist[i - 1][j - 1] + 1);
+            }
+
+            b_prior = b;
+        }
+        a_prior = a;
+    }
+
+    if tracing::enabled!(Level::TRACE) {
+        // logging the beautified 2D-matrix as a single event keeps the matrices of multiple threads apart
+        trace!(
+            "DP matrix:\n{}",
+            format!("{:?}", dist).replace("], [", "],\n[")
+        );
+    }
+
+    dist[str_a.len()][str_b.len()]
+}
+
+/// Calculates the distance like [`calculate_osa_distance_between_two_strings`], but with the edits weighted by the
+/// cost matrix. Only two rows of the DP matrix are kept besides the one being calculated.
+pub fn calculate_weighted_osa_distance(str_a: &[u8], str_b: &[u8], costs: &CostMatrix) -> u32 {
+    let unit = costs.unit;
+    let mut before_prev: Vec<u32> = vec![0; str_b.len() + 1];
+    let mut prev: Vec<u32> = (0..=str_b.len() as u32).map(|j| j * unit).collect();
+    let mut row: Vec<u32> = vec![0; str_b.len() + 1];
+    for (i, &a) in str_a.iter().enumerate() {
+        row[0] = (i as u32 + 1) * unit;
+        for (j, &b) in str_b.iter().enumerate() {
+            row[j + 1] = (prev[j + 1] + unit) // deletion
+                .min(row[j] + unit) // insertion
+                .min(prev[j] + costs.substitution(a, b)); // substitution
+            if i > 0 && j > 0 && a == str_b[j - 1] && str_a[i - 1] == b {
+                // transposition
+                row[j + 1] = row[j + 1].min(before_prev[j - 1].saturating_add(costs.transposition));
+            }
+        }
+        std::mem::swap(&mut before_prev, &mut prev);
+        std::mem::swap(&mut prev, &mut row);
+    }
+    prev[str_b.len()]
+}
+
+/// Returns the amount of distance units a single edit counts, which is more than one if the edits are weighted.
+pub fn edit_cost_unit() -> u32 {
+    DistanceSettings::current().edit_cost_unit()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Returns pairs covering empty, identical and long lines as well as random ones over a small alphabet, which
+    /// are likely to hold transpositions and common affixes.
+    pub(crate) fn sample_pairs() -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut random_line = |max_len: u64| -> Vec<u8> {
+            let mut next = || {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            };
+            let len = next() % (max_len + 1);
+            (0..len).map(|_| b"ab c,"[(next() % 5) as usize]).collect()
+        };
+        let long_line =
+            |len: usize| -> Vec<u8> { (0..len).map(|i| b'a' + (i % 7) as u8).collect() };
+
+        let mut pairs = vec![
+            (Vec::new(), Vec::new()),
+            (Vec::new(), b"abc".to_vec()),
+            (b"abc".to_vec(), Vec::new()),
+            (b"abcd".to_vec(), b"abcd".to_vec()),
+            (b"abcd".to_vec(), b"acbd".to_vec()),
+            (b"ca".to_vec(), b"abc".to_vec()),
+            (long_line(1000), long_line(1000)),
+            (long_line(1000), long_line(700)),
+        ];
+        let mut mutated = long_line(1000);
+        mutated.swap(10, 11);
+        mutated[500] = b'z';
+        mutated.remove(900);
+        pairs.push((long_line(1000), mutated));
+        for _ in 0..300 {
+            pairs.push((random_line(24), random_line(24)));
+        }
+        for _ in 0..10 {
+            pairs.push((random_line(400), random_line(400)));
+        }
+        pairs
+    }
+
+    fn settings(kernel: Kernel, costs: Option<&'static CostMatrix>) -> DistanceSettings {
+        DistanceSettings {
+            algorithm: Algorithm::Osa,
+            kernel,
+            costs,
+        }
+    }
+
+    #[test]
+    fn stripping_common_affixes_keeps_the_distance() {
+        let scalar = settings(Kernel::Scalar, None);
+        for (a, b) in sample_pairs() {
+            assert_eq!(
+                calculate_edit_distance(&a, &b, &scalar),
+                calculate_osa_distance_between_two_strings(&a, &b),
+                "{:?} vs. {:?}",
+                String::from_utf8_lossy(&a),
+                String::from_utf8_lossy(&b)
+            );
+        }
+    }
+
+    #[test]
+    fn common_affixes_are_stripped() {
+        assert_eq!(
+            strip_common_affixes(b"abcXYdef", b"abcYXdef"),
+            (&b"XY"[..], &b"YX"[..])
+        );
+        assert_eq!(strip_common_affixes(b"aa", b"aaa"), (&b""[..], &b"a"[..]));
+        assert_eq!(strip_common_affixes(b"abc", b"abc"), (&b""[..], &b""[..]));
+    }
+
+    #[test]
+    fn uniform_costs_equal_the_scalar_kernel() {
+        let (single, double) = (CostMatrix::uniform(1), CostMatrix::uniform(2));
+        for (a, b) in sample_pairs() {
+            let expected = calculate_osa_distance_between_two_strings(&a, &b);
+            assert_eq!(calculate_weighted_osa_distance(&a, &b, &single), expected);
+            assert_eq!(
+                calculate_weighted_osa_distance(&a, &b, &double),
+                2 * expected
+            );
+        }
+    }
+
+    #[test]
+    fn typos_of_adjacent_keys_cost_half_an_edit() {
+        let costs = TypoModel::Qwerty.cost_matrix();
+        assert_eq!(
+            calculate_weighted_osa_distance(b"hello", b"hwllo", &costs),
+            1
+        );
+        assert_eq!(
+            calculate_weighted_osa_distance(b"hello", b"hWllo", &costs),
+            1
+        );
+        assert_eq!(
+            calculate_weighted_osa_distance(b"hello", b"hzllo", &costs),
+            2
+        );
+        assert_eq!(
+            calculate_weighted_osa_distance(b"hello", b"helo", &costs),
+            2
+        );
+        assert_eq!(calculate_weighted_osa_distance(b"", b"", &costs), 0);
+        let dvorak = TypoModel::Dvorak.cost_matrix();
+        assert_eq!(calculate_weighted_osa_distance(b"aoe", b"aou", &dvorak), 1);
+    }
+
+    #[test]
+    fn transpositions_cost_as_configured() {
+        let mut costs = CostMatrix::uniform(2);
+        costs.transposition = 1;
+        assert_eq!(calculate_weighted_osa_distance(b"abcd", b"acbd", &costs), 1);
+        assert_eq!(calculate_weighted_osa_distance(b"ab", b"ba", &costs), 1);
+        // more expensive than two substitutions, which are taken instead
+        costs.transposition = 5;
+        assert_eq!(calculate_weighted_osa_distance(b"abcd", b"acbd", &costs), 4);
+        costs.transposition = u32::MAX;
+        assert_eq!(calculate_weighted_osa_distance(b"abcd", b"acbd", &costs), 4);
+        assert_eq!(calculate_weighted_osa_distance(b"abcd", b"abcd", &costs), 0);
+        assert_eq!(calculate_weighted_osa_distance(b"", b"ab", &costs), 4);
+    }
+
+    #[test]
+    fn without_transpositions_the_distance_is_levenshtein() {
+        let mut costs = CostMatrix::uniform(1);
+        costs.transposition = u32::MAX;
+        let levenshtein = |a: &[u8], b: &[u8]| -> u32 {
+            let mut prev: Vec<u32> = (0..=b.len() as u32).collect();
+            for (i, &ca) in a.iter().enumerate() {
+                let mut row = vec![i as u32 + 1; b.len() + 1];
+                for (j, &cb) in b.iter().enumerate() {
+                    row[j + 1] = (prev[j + 1] + 1)
+                        .min(row[j] + 1)
+                        .min(prev[j] + u32::from(ca != cb));
+                }
+                prev = row;
+            }
+            prev[b.len()]
+        };
+        for (a, b) in sample_pairs() {
+            assert_eq!(
+                calculate_weighted_osa_distance(&a, &b, &costs),
+                levenshtein(&a, &b)
+            );
+        }
+    }
+
+    #[test]
+    fn token_set_distance_ignores_the_order_of_tokens() {
+        let scalar = settings(Kernel::Scalar, None);
+        let distance = |a: &[u8], b: &[u8]| calculate_token_set_distance(a, b, &scalar);
+        assert_eq!(distance(b"john smith", b"smith john"), 0);
+        assert_eq!(distance(b"a,b;c|d", b"d c  b a"), 0);
+        assert_eq!(distance(b"new york", b"new york city"), 0);
+        assert_eq!(distance(b"foo", b"bar"), 3);
+        assert_eq!(distance(b"", b""), 0);
+        assert_eq!(distance(b"", b"abc"), 3);
+        assert_eq!(distance(b"smith john", b"smyth jon"), 2);
+    }
+
+    #[test]
+    fn token_set_distance_is_the_same_with_every_kernel() {
+        let (scalar, simd) = (settings(Kernel::Scalar, None), settings(Kernel::Simd, None));
+        for (a, b) in sample_pairs() {
+            assert_eq!(
+                calculate_token_set_distance(&a, &b, &simd),
+                calculate_token_set_distance(&a, &b, &scalar)
+            );
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Result sink writing every calculated pair as a row into a columnar Parquet file.
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::types::UInt64Type;
+use arrow_array::{
+    ArrayRef, DictionaryArray, Float32Array, RecordBatch, StringArray, UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::Result;
+use parquet::file::properties::WriterProperties;
+
+use dldist::engine::DistanceResult;
+
+/// Amount of buffered pairs which are written out together as one record batch.
+const BATCH_SIZE: usize = 1 << 20;
+
+pub struct ParquetSink {
+    writer: ArrowWriter<File>,
+    schema: SchemaRef,
+    /// Labels of all lines, the dictionary of both line columns.
+    labels: ArrayRef,
+    line_a: Vec<u64>,
+    line_b: Vec<u64>,
+    dist: Vec<u32>,
+    norm: Vec<f32>,
+}
+
+impl ParquetSink {
+    /// Creates the file with the columns `line_a`, `line_b` (both labels of the lines as printed in the results, e.g.
+    /// their line numbers in the input file), `dist` and `norm`. The line columns are dictionary-encoded with the
+    /// given labels of all lines, so that every row only stores the indices of its lines.
+    pub fn create(path: &Path, labels: &[String]) -> Result<Self> {
+        let label_type = DataType::Dictionary(Box::new(DataType::UInt64), Box::new(DataType::Utf8));
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("line_a", label_type.clone(), false),
+            Field::new("line_b", label_type, false),
+            Field::new("dist", DataType::UInt32, false),
+            Field::new("norm", DataType::Float32, false),
+        ]));
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(props))?;
+        Ok(ParquetSink {
+            writer,
+            schema,
+            labels: Arc::new(StringArray::from_iter_values(labels)),
+            line_a: Vec::with_capacity(BATCH_SIZE),
+            line_b: Vec::with_capacity(BATCH_SIZE),
+            dist: Vec::with_capacity(BATCH_SIZE),
+            norm: Vec::with_capacity(BATCH_SIZE),
+        })
+    }
+
+    pub fn insert(&mut self, dr: &DistanceResult) -> Result<()> {
+        self.line_a.push(dr.line_a);
+        self.line_b.push(dr.line_b);
+        self.dist.push(dr.dldist);
+        self.norm.push(dr.normalized_dldist);
+        if self.line_a.len() >= BATCH_SIZE {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<()> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(label_column(
+                std::mem::take(&mut self.line_a),
+                &self.labels,
+            )?),
+            Arc::new(label_column(
+                std::mem::take(&mut self.line_b),
+                &self.labels,
+            )?),
+            Arc::new(UInt32Array::from(std::mem::take(&mut self.dist))),
+            Arc::new(Float32Array::from(std::mem::take(&mut self.norm))),
+        ];
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)?;
+        self.writer.write(&batch)
+    }
+
+    /// Writes the remaining pairs and the file footer.
+    pub fn finish(mut self) -> Result<()> {
+        if !self.line_a.is_empty() {
+            self.write_batch()?;
+        }
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Returns the column of the labels of the given lines, referring to them by their indices into the labels.
+fn label_column(lines: Vec<u64>, labels: &ArrayRef) -> Result<DictionaryArray<UInt64Type>> {
+    Ok(DictionaryArray::try_new(
+        UInt64Array::from(lines),
+        labels.clone(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use dldist::engine::Settings;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn labels_are_read_back_from_the_dictionary() {
+        let path = std::env::temp_dir().join(format!("dldist-{}.parquet", std::process::id()));
+        let labels = ["1", "2", "3"].map(String::from);
+        let mut sink = ParquetSink::create(&path, &labels).unwrap();
+        let settings = Settings::current();
+        sink.insert(&DistanceResult::new(0, 2, 3, 4, 1, &settings))
+            .unwrap();
+        sink.insert(&DistanceResult::new(1, 2, 4, 4, 2, &settings))
+            .unwrap();
+        sink.finish().unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+        std::fs::remove_file(&path).unwrap();
+        let batch = &batches[0];
+        let texts = |column: usize| -> Vec<String> {
+            let column = batch.column(column).as_dictionary::<UInt64Type>();
+            let values = column.values().as_string::<i32>();
+            column
+                .keys()
+                .values()
+                .iter()
+                .map(|&key| values.value(key as usize).to_string())
+                .collect()
+        };
+        assert_eq!(texts(0), ["1", "2"]);
+        assert_eq!(texts(1), ["3", "3"]);
+        assert_eq!(
+            batch
+                .column(2)
+                .as_primitive::<arrow_array::types::UInt32Type>()
+                .values(),
+            &[1, 2]
+        );
+    }
+}
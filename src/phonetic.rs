@@ -0,0 +1,226 @@
+//! Phonetic encodings of words, so that differently spelled but similarly sounding words compare as equal.
+//!
+//! Both encodings only consider the ASCII letters of a word, all other bytes are dropped.
+
+fn ascii_letters(word: &[u8]) -> Vec<u8> {
+    word.iter()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn soundex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'B' | b'F' | b'P' | b'V' => Some(b'1'),
+        b'C' | b'G' | b'J' | b'K' | b'Q' | b'S' | b'X' | b'Z' => Some(b'2'),
+        b'D' | b'T' => Some(b'3'),
+        b'L' => Some(b'4'),
+        b'M' | b'N' => Some(b'5'),
+        b'R' => Some(b'6'),
+        _ => None,
+    }
+}
+
+/// Returns the American Soundex code of the word, e.g. `R163` for both `Robert` and `Rupert`. Words without letters
+/// result in an empty code.
+pub fn soundex(word: &[u8]) -> Vec<u8> {
+    let letters = ascii_letters(word);
+    let Some(&first) = letters.first() else {
+        return Vec::new();
+    };
+    let mut code = vec![first];
+    let mut last_digit = soundex_digit(first);
+    for &c in &letters[1..] {
+        let digit = soundex_digit(c);
+        if let Some(d) = digit.filter(|_| digit != last_digit) {
+            code.push(d);
+            if code.len() == 4 {
+                break;
+            }
+        }
+        // H and W do not separate equal digits, while vowels do
+        if c != b'H' && c != b'W' {
+            last_digit = digit;
+        }
+    }
+    code.resize(4, b'0');
+    code
+}
+
+fn is_vowel(c: u8) -> bool {
+    matches!(c, b'A' | b'E' | b'I' | b'O' | b'U')
+}
+
+/// Returns the (original) Metaphone code of the word, e.g. `SM0` for both `Smith` and `Smyth`, with `0` standing for
+/// `th`. Words without letters result in an empty code.
+pub fn metaphone(word: &[u8]) -> Vec<u8> {
+    let mut letters = ascii_letters(word);
+    // initial letter exceptions
+    match letters.as_slice() {
+        [b'A', b'E', ..] | [b'G' | b'K' | b'P', b'N', ..] | [b'W', b'R', ..] => {
+            letters.remove(0);
+        }
+        [b'X', ..] => letters[0] = b'S',
+        [b'W', b'H', ..] => {
+            letters.remove(1);
+        }
+        _ => {}
+    }
+
+    let at = |i: usize| letters.get(i).copied().unwrap_or(0);
+    let mut code = Vec::new();
+    let mut i = 0;
+    while i < letters.len() {
+        let c = letters[i];
+        let (prev, next, after_next) = (if i > 0 { at(i - 1) } else { 0 }, at(i + 1), at(i + 2));
+        let is_last = i + 1 == letters.len();
+        // doubled letters are only encoded once, except for C
+        if c == prev && c != b'C' {
+            i += 1;
+            continue;
+        }
+        match c {
+            b'A' | b'E' | b'I' | b'O' | b'U' => {
+                if i == 0 {
+                    code.push(c);
+                }
+            }
+            b'B' => {
+                if !(is_last && prev == b'M') {
+                    code.push(b'B');
+                }
+            }
+            b'C' => {
+                if next == b'I' && after_next == b'A' {
+                    code.push(b'X');
+                } else if next == b'H' {
+                    code.push(if prev == b'S' { b'K' } else { b'X' });
+                    i += 1;
+                } else if matches!(next, b'I' | b'E' | b'Y') {
+                    if prev != b'S' {
+                        code.push(b'S');
+                    }
+                } else {
+                    code.push(b'K');
+                }
+            }
+            b'D' => {
+                if next == b'G' && matches!(after_next, b'E' | b'I' | b'Y') {
+                    code.push(b'J');
+                    i += 2;
+                } else {
+                    code.push(b'T');
+                }
+            }
+            b'G' => {
+                let silent_gh = next == b'H' && !(i + 2 == letters.len() || is_vowel(after_next));
+                let silent_gn = next == b'N'
+                    && (i + 2 == letters.len() || letters[i + 1..].starts_with(b"NED"));
+                if silent_gh || silent_gn {
+                    // silent
+                } else if matches!(next, b'I' | b'E' | b'Y') && prev != b'G' {
+                    code.push(b'J');
+                } else {
+                    code.push(b'K');
+                }
+            }
+            b'H' => {
+                let silent = is_last
+                    || (i > 0 && is_vowel(prev) && !is_vowel(next))
+                    || matches!(prev, b'C' | b'S' | b'P' | b'T' | b'G');
+                if !silent {
+                    code.push(b'H');
+                }
+            }
+            b'K' => {
+                if prev != b'C' {
+                    code.push(b'K');
+                }
+            }
+            b'P' => {
+                if next == b'H' {
+                    code.push(b'F');
+                    i += 1;
+                } else {
+                    code.push(b'P');
+                }
+            }
+            b'Q' => code.push(b'K'),
+            b'S' => {
+                if next == b'H' {
+                    code.push(b'X');
+                    i += 1;
+                } else if next == b'I' && matches!(after_next, b'O' | b'A') {
+                    code.push(b'X');
+                } else {
+                    code.push(b'S');
+                }
+            }
+            b'T' => {
+                if next == b'I' && matches!(after_next, b'O' | b'A') {
+                    code.push(b'X');
+                } else if next == b'H' {
+                    code.push(b'0');
+                    i += 1;
+                } else if !(next == b'C' && after_next == b'H') {
+                    code.push(b'T');
+                }
+            }
+            b'V' => code.push(b'F'),
+            b'W' | b'Y' => {
+                if is_vowel(next) {
+                    code.push(c);
+                }
+            }
+            b'X' => code.extend_from_slice(b"KS"),
+            b'Z' => code.push(b'S'),
+            // F, J, L, M, N and R are encoded as themselves
+            _ => code.push(c),
+        }
+        i += 1;
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soundex_codes() {
+        for (word, code) in [
+            ("Robert", "R163"),
+            ("Rupert", "R163"),
+            ("Rubin", "R150"),
+            ("Ashcraft", "A261"),
+            ("Tymczak", "T522"),
+            ("Pfister", "P236"),
+            ("Lee", "L000"),
+            ("o'Hara", "O600"),
+            ("42", ""),
+        ] {
+            assert_eq!(soundex(word.as_bytes()), code.as_bytes(), "{word}");
+        }
+    }
+
+    #[test]
+    fn metaphone_codes() {
+        for (word, code) in [
+            ("Smith", "SM0"),
+            ("Smyth", "SM0"),
+            ("Knight", "NT"),
+            ("Wright", "RT"),
+            ("Xavier", "SFR"),
+            ("Phone", "FN"),
+            ("Church", "XRX"),
+            ("Aeon", "EN"),
+            ("", ""),
+        ] {
+            assert_eq!(
+                String::from_utf8(metaphone(word.as_bytes())).unwrap(),
+                code,
+                "{word}"
+            );
+        }
+    }
+}
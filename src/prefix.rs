@@ -0,0 +1,260 @@
+//! Calculation of the distances of all pairs of lines which shares the DP rows of common prefixes, as in trie-based
+//! Levenshtein search.
+//!
+//! The unique lines are sorted, so that consecutive lines tend to share long prefixes, e.g. in sorted wordlists or
+//! log lines of the same template. Every line is then compared against all lines sorted before it, keeping the rows of
+//! the DP matrix which belong to the prefix shared with the previous line. Duplicate lines are only calculated once.
+
+use std::cmp::Ordering;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+
+use threadpool::ThreadPool;
+
+use crate::engine::{DistanceResult, Settings};
+use crate::osa::CostMatrix;
+
+/// Amount of DP cells of the columns calculated by a single job.
+const JOB_CELLS: usize = 1 << 22;
+
+/// Work done by the batched calculation.
+#[derive(Clone, Copy, Default)]
+pub struct PrefixStats {
+    /// Amount of unique lines, which is the amount of columns.
+    pub unique_lines: usize,
+    /// DP cells calculated.
+    pub cells: u64,
+    /// DP cells the full matrices of all pairs of unique lines would have.
+    pub full_cells: u64,
+}
+
+fn shared_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+/// Returns the distances of the line `b` to all `rows` lines, which are sorted and come with the length of the prefix
+/// shared with their predecessor (in `shared_lens`). Also returns the amount of calculated cells and the amount of
+/// cells of the full matrices.
+fn column_distances(
+    b: &[u8],
+    rows: &[Vec<u8>],
+    shared_lens: &[usize],
+    costs: Option<&CostMatrix>,
+) -> (Vec<u32>, u64, u64) {
+    let (unit, transposition) = costs.map_or((1, 1), |c| (c.unit, c.transposition));
+    let width = b.len() + 1;
+    let max_len = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut dist = vec![0u32; (max_len + 1) * width];
+    for (j, cell) in dist[..width].iter_mut().enumerate() {
+        *cell = j as u32 * unit;
+    }
+
+    let mut valid_rows = 0;
+    let (mut cells, mut full_cells) = (0u64, 0u64);
+    let mut distances = Vec::with_capacity(rows.len());
+    for (a, &shared_len) in rows.iter().zip(shared_lens) {
+        for i in shared_len.min(valid_rows) + 1..=a.len() {
+            let (before, row) = dist.split_at_mut(i * width);
+            let row = &mut row[..width];
+            let prev = &before[(i - 1) * width..];
+            row[0] = i as u32 * unit;
+            for j in 1..width {
+                let substitution = match costs {
+                    Some(c) => c.substitution(a[i - 1], b[j - 1]),
+                    None => (a[i - 1] != b[j - 1]) as u32,
+                };
+                row[j] = (prev[j] + unit) // deletion
+                    .min(row[j - 1] + unit) // insertion
+                    .min(prev[j - 1] + substitution); // substitution
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    // transposition
+                    let before_prev = before[(i - 2) * width + j - 2];
+                    row[j] = row[j].min(before_prev.saturating_add(transposition));
+                }
+            }
+            cells += b.len() as u64;
+        }
+        full_cells += (a.len() * b.len()) as u64;
+        valid_rows = a.len();
+        distances.push(dist[a.len() * width + b.len()]);
+    }
+    (distances, cells, full_cells)
+}
+
+/// Calculates the OSA distances of all pairs of the lines, in the order of `window_pairs` over all lines. The edits
+/// are weighted by `COST_MATRIX` if set, the kernel and the algorithm are not taken into account.
+pub fn calculate_all_osa_distances(lines: &[Vec<u8>]) -> (Vec<DistanceResult>, PrefixStats) {
+    calculate_all_osa_distances_with(lines, &Settings::current())
+}
+
+/// Calculates the distances like [`calculate_all_osa_distances`], but with the given settings instead of the globals.
+pub fn calculate_all_osa_distances_with(
+    lines: &[Vec<u8>],
+    settings: &Settings,
+) -> (Vec<DistanceResult>, PrefixStats) {
+    let mut order: Vec<usize> = (0..lines.len()).collect();
+    order.sort_by(|&a, &b| lines[a].cmp(&lines[b]));
+    // position of every line among the sorted unique lines
+    let mut unique_ids = vec![0usize; lines.len()];
+    let mut unique: Vec<Vec<u8>> = Vec::new();
+    for &i in &order {
+        if unique.last() != Some(&lines[i]) {
+            unique.push(lines[i].clone());
+        }
+        unique_ids[i] = unique.len() - 1;
+    }
+    let shared_lens: Vec<usize> = (0..unique.len())
+        .map(|k| {
+            k.checked_sub(1)
+                .map_or(0, |p| shared_prefix_len(&unique[p], &unique[k]))
+        })
+        .collect();
+    let unique_cnt = unique.len();
+    let (unique, shared_lens) = (Arc::new(unique), Arc::new(shared_lens));
+    let costs: Option<&'static CostMatrix> = settings.distance.costs;
+
+    // distances of unique line `v` to all `u < v`, starting at `v * (v - 1) / 2`
+    let mut triangle = vec![0u32; unique_cnt * unique_cnt.saturating_sub(1) / 2];
+    let mut stats = PrefixStats {
+        unique_lines: unique_cnt,
+        ..PrefixStats::default()
+    };
+    let pool = ThreadPool::new(settings.thread_num);
+    let (tx, rx) = channel::<Vec<(usize, Vec<u32>, u64, u64)>>();
+    let mut v = 1;
+    while v < unique_cnt {
+        // columns are batched until the job has enough cells, as later columns have more rows
+        let start = v;
+        let mut job_cells = 0;
+        while v < unique_cnt && (v == start || job_cells < JOB_CELLS) {
+            job_cells += v * unique[v].len().max(1);
+            v += 1;
+        }
+        let (end, tx_child) = (v, tx.clone());
+        let (unique, shared_lens) = (Arc::clone(&unique), Arc::clone(&shared_lens));
+        pool.execute(move || {
+            let results = (start..end)
+                .map(|col| {
+                    let (distances, cells, full_cells) =
+                        column_distances(&unique[col], &unique[..col], &shared_lens, costs);
+                    (col, distances, cells, full_cells)
+                })
+                .collect();
+            tx_child.send(results).unwrap();
+        });
+    }
+    drop(tx);
+    for (col, distances, cells, full_cells) in rx.iter().flatten() {
+        let offset = col * (col - 1) / 2;
+        triangle[offset..offset + col].copy_from_slice(&distances);
+        stats.cells += cells;
+        stats.full_cells += full_cells;
+    }
+    pool.join();
+
+    let results = crate::engine::window_pairs(lines.len(), lines.len())
+        .map(|(la, lb)| {
+            let (u, v) = (unique_ids[la], unique_ids[lb]);
+            let distance = match u.cmp(&v) {
+                Ordering::Equal => 0,
+                Ordering::Less => triangle[v * (v - 1) / 2 + u],
+                Ordering::Greater => triangle[u * (u - 1) / 2 + v],
+            };
+            DistanceResult::new(
+                la as u64,
+                lb as u64,
+                lines[la].len(),
+                lines[lb].len(),
+                distance,
+                settings,
+            )
+        })
+        .collect();
+    (results, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{window_pairs, Normalization};
+    use crate::osa::tests::sample_pairs;
+    use crate::osa::{
+        calculate_osa_distance_between_two_strings, calculate_weighted_osa_distance,
+        DistanceSettings, TypoModel,
+    };
+
+    /// Returns short lines with common prefixes and duplicates, along with an empty and a long one.
+    fn sample_lines() -> Vec<Vec<u8>> {
+        let mut lines: Vec<Vec<u8>> = sample_pairs()
+            .into_iter()
+            .flat_map(|(a, b)| [a, b])
+            .filter(|line| line.len() <= 24)
+            .take(200)
+            .collect();
+        let mut long_line = b"b".to_vec();
+        long_line.resize(2000, b'z');
+        lines.insert(lines.len() / 2, long_line);
+        lines
+    }
+
+    fn settings(costs: Option<&'static CostMatrix>) -> Settings {
+        Settings {
+            thread_num: 2,
+            normalization: Normalization::Mean,
+            as_similarity: false,
+            distance: DistanceSettings {
+                costs,
+                ..DistanceSettings::default()
+            },
+        }
+    }
+
+    /// Asserts that the batched distances are the given ones of all pairs, in the order of `window_pairs`.
+    fn assert_distances(
+        lines: &[Vec<u8>],
+        settings: &Settings,
+        distance: impl Fn(&[u8], &[u8]) -> u32,
+    ) {
+        let (results, stats) = calculate_all_osa_distances_with(lines, settings);
+        assert!(stats.cells <= stats.full_cells);
+        let pairs: Vec<(usize, usize)> = window_pairs(lines.len(), lines.len()).collect();
+        assert_eq!(results.len(), pairs.len());
+        for (dr, (la, lb)) in results.iter().zip(pairs) {
+            assert_eq!((dr.line_a, dr.line_b), (la as u64, lb as u64));
+            // the shorter line first keeps the full matrix small
+            let (a, b) = (&lines[la], &lines[lb]);
+            let (a, b) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+            assert_eq!(dr.dldist, distance(a, b), "{la} vs. {lb}");
+        }
+    }
+
+    #[test]
+    fn batched_distances_equal_the_scalar_kernel() {
+        let lines = sample_lines();
+        assert!(lines.iter().any(Vec::is_empty));
+        assert_distances(
+            &lines,
+            &settings(None),
+            calculate_osa_distance_between_two_strings,
+        );
+    }
+
+    #[test]
+    fn batched_weighted_distances_equal_the_weighted_kernel() {
+        let mut costs = TypoModel::Qwerty.cost_matrix();
+        costs.transposition = 3;
+        let costs: &'static CostMatrix = Box::leak(Box::new(costs));
+        let lines = sample_lines();
+        assert_distances(&lines, &settings(Some(costs)), |a, b| {
+            calculate_weighted_osa_distance(a, b, costs)
+        });
+    }
+
+    #[test]
+    fn fewer_than_two_lines_have_no_pairs() {
+        let (results, _) = calculate_all_osa_distances_with(&[b"abc".to_vec()], &settings(None));
+        assert!(results.is_empty());
+        let (results, _) = calculate_all_osa_distances_with(&[], &settings(None));
+        assert!(results.is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+//! Python bindings of the distance and pairwise engine, built as the `dldist` extension module with maturin.
+//!
+//! ```python
+//! import dldist
+//! dldist.osa("abcd", "acbd")  # 1
+//! dldist.pairwise(["foo", "fo0", "bar"], normalize_by="max", top=2)  # [(0, 1, 1, 0.33...), (0, 2, 3, 1.0)]
+//! ```
+
+use clap::ValueEnum;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::engine::{
+    calculate_osa_distances_with, sort_distance_results, window_pairs, Normalization, Settings,
+};
+use crate::osa::{calculate_distance_with, DistanceSettings};
+
+/// Returns the optimal string alignment distance of both strings, counted in bytes of their UTF-8 encoding.
+#[pyfunction]
+fn osa(a: &str, b: &str) -> u32 {
+    calculate_distance_with(a.as_bytes(), b.as_bytes(), &DistanceSettings::default())
+}
+
+/// Calculates the distances of all pairs of lines, optionally only within a window of following lines, and returns
+/// them sorted as `(line_a, line_b, dist, norm)` tuples with 0-based line indices. The pairs are sorted by the
+/// normalized distance if `normalize_by` names a strategy of `--normalize-by`, otherwise by the distance.
+#[pyfunction]
+#[pyo3(signature = (lines, *, window=None, normalize_by=None, descending=false, threads=1, top=None))]
+fn pairwise(
+    py: Python<'_>,
+    lines: Vec<String>,
+    window: Option<usize>,
+    normalize_by: Option<&str>,
+    descending: bool,
+    threads: usize,
+    top: Option<usize>,
+) -> PyResult<Vec<(u64, u64, u32, f32)>> {
+    if threads == 0 {
+        return Err(PyValueError::new_err("threads has to be at least 1"));
+    }
+    let normalization = normalize_by
+        .map(|name| Normalization::from_str(name, true).map_err(PyValueError::new_err))
+        .transpose()?;
+
+    // the settings of the call are passed along instead of set globally, as other threads might call concurrently
+    let settings = Settings {
+        thread_num: threads,
+        normalization: normalization.unwrap_or(Normalization::Mean),
+        as_similarity: false,
+        distance: DistanceSettings::default(),
+    };
+    let lines: Vec<Vec<u8>> = lines.into_iter().map(String::into_bytes).collect();
+    let window = window.unwrap_or(lines.len());
+    let mut results = py.detach(|| {
+        let mut results = calculate_osa_distances_with(
+            &lines,
+            &lines,
+            window_pairs(lines.len(), window),
+            &settings,
+        );
+        sort_distance_results(&mut results, normalization.is_some(), descending, &settings);
+        results
+    });
+    if let Some(top) = top {
+        results.truncate(top);
+    }
+    Ok(results
+        .iter()
+        .map(|dr| (dr.line_a, dr.line_b, dr.dldist, dr.normalized_dldist))
+        .collect())
+}
+
+#[pymodule]
+fn dldist(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(osa, m)?)?;
+    m.add_function(wrap_pyfunction!(pairwise, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! Aggregation of the distances of every line to the lines it has been compared with.
+
+use dldist::engine::DistanceResult;
+
+/// Mean, minimum and maximum of the distances of a line to all of its compared partners.
+#[derive(Clone, Copy)]
+pub struct LineScore {
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+    pub partners_cnt: usize,
+}
+
+/// Returns the score of every line, `None` for lines which have not been compared. `distance` picks the value of a
+/// pair which is aggregated, e.g. its normalized distance.
+pub fn line_scores(
+    lines_cnt: usize,
+    distance_results: &[DistanceResult],
+    distance: impl Fn(&DistanceResult) -> f32,
+) -> Vec<Option<LineScore>> {
+    // summing up in f64 keeps the mean of many partners precise
+    let mut sums = vec![0f64; lines_cnt];
+    let mut scores: Vec<Option<LineScore>> = vec![None; lines_cnt];
+    // the results are in the order the threads finished them, summing them up in the order of the pairs instead keeps
+    // the rounding and thus the means the same between runs
+    let mut ordered: Vec<&DistanceResult> = distance_results.iter().collect();
+    ordered.sort_unstable_by_key(|dr| (dr.line_a, dr.line_b));
+    for dr in ordered {
+        let value = distance(dr);
+        for line in [dr.line_a as usize, dr.line_b as usize] {
+            sums[line] += value as f64;
+            let score = scores[line].get_or_insert(LineScore {
+                mean: 0f32,
+                min: value,
+                max: value,
+                partners_cnt: 0,
+            });
+            score.min = score.min.min(value);
+            score.max = score.max.max(value);
+            score.partners_cnt += 1;
+        }
+    }
+    for (score, sum) in scores.iter_mut().zip(sums) {
+        if let Some(score) = score {
+            score.mean = (sum / score.partners_cnt as f64) as f32;
+        }
+    }
+    scores
+}
@@ -0,0 +1,334 @@
+//! Long-running HTTP server answering distance queries with JSON, so that callers do not have to spawn a process per
+//! query:
+//! ```text
+//! GET  /distance?a=<line>&b=<line>    -> {"distance": 1}
+//! POST /pairwise {"lines": [...], "window": 2, "normalize_by": "max", "descending": false, "top": 10}
+//!                                     -> {"pairs": [{"line_a": 0, "line_b": 1, "dist": 1, "norm": 0.33}, ...]}
+//! ```
+//! Only `lines` is required for `/pairwise`. Every connection is handled on its own thread, up to a limit of
+//! concurrent connections, while the pairwise calculations share one thread pool whose threads take turns on their
+//! jobs.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::num::NonZero;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use threadpool::ThreadPool;
+
+use dldist::engine::{
+    for_each_osa_distance_in, sort_distance_results, window_combinations_count, window_pairs,
+    Normalization, Settings,
+};
+use dldist::osa::{calculate_osa_distance, Kernel, KERNEL};
+use dldist::simd;
+
+/// Upper limit of the size of a request body, which keeps a single request from exhausting the memory.
+const MAX_BODY_LEN: usize = 64 << 20;
+/// Upper limit of the size of the request line and headers together.
+const MAX_HEAD_LEN: u64 = 16 << 10;
+/// Upper limit of the pairs of a single `/pairwise` request, which keeps it from occupying the threads for long.
+const MAX_PAIRS: u64 = 50_000_000;
+/// Upper limit of the connections handled at the same time, further ones are answered with 503 right away.
+const MAX_CONNECTIONS: usize = 64;
+/// Time a connection may take to send its request or to receive the response before it is dropped.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Args)]
+pub struct ServeArguments {
+    /// Port to listen on.
+    #[arg(short = 'p', long, default_value_t = 8080)]
+    port: u16,
+
+    /// Address to bind to. Use 0.0.0.0 to accept connections from other hosts.
+    #[arg(long, default_value = "127.0.0.1")]
+    bind: String,
+
+    /// Amount of threads calculating the pairs of a request. Defaults to all-but-one available cores.
+    #[arg(short = 'j', long)]
+    thread_num: Option<NonZero<usize>>,
+}
+
+#[derive(Deserialize)]
+struct PairwiseRequest {
+    lines: Vec<String>,
+    window: Option<NonZero<usize>>,
+    normalize_by: Option<String>,
+    #[serde(default)]
+    descending: bool,
+    top: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Pair {
+    line_a: u64,
+    line_b: u64,
+    dist: u32,
+    norm: f32,
+}
+
+struct Response {
+    status: &'static str,
+    body: serde_json::Value,
+}
+
+impl Response {
+    fn ok(body: serde_json::Value) -> Self {
+        Response {
+            status: "200 OK",
+            body,
+        }
+    }
+
+    fn error(status: &'static str, message: impl Into<String>) -> Self {
+        Response {
+            status,
+            body: serde_json::json!({ "error": message.into() }),
+        }
+    }
+}
+
+/// Slot of a connection being handled, which is freed when dropped, even if handling the connection panicked.
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Decodes a `application/x-www-form-urlencoded` component, e.g. `a%20b+c` to `a b c`.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match bytes
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| percent_decode(key) == name)
+        .map(|(_, value)| percent_decode(value))
+}
+
+fn distance(query: &str) -> Response {
+    match (query_param(query, "a"), query_param(query, "b")) {
+        (Some(a), Some(b)) => Response::ok(serde_json::json!({
+            "distance": calculate_osa_distance(a.as_bytes(), b.as_bytes())
+        })),
+        _ => Response::error(
+            "400 Bad Request",
+            "both query parameters a and b are required",
+        ),
+    }
+}
+
+fn pairwise(body: &[u8], pool: &ThreadPool) -> Response {
+    let request: PairwiseRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(error) => return Response::error("400 Bad Request", error.to_string()),
+    };
+    let normalization = match request
+        .normalize_by
+        .as_deref()
+        .map(|name| Normalization::from_str(name, true))
+        .transpose()
+    {
+        Ok(normalization) => normalization,
+        Err(error) => return Response::error("400 Bad Request", error),
+    };
+
+    let lines: Vec<Vec<u8>> = request.lines.into_iter().map(String::into_bytes).collect();
+    let window = request.window.map_or(lines.len(), NonZero::get);
+    if window_combinations_count(lines.len() as u64, window as u64) > MAX_PAIRS {
+        return Response::error(
+            "413 Payload Too Large",
+            format!(
+                "too many pairs, at most {} are calculated per request",
+                MAX_PAIRS
+            ),
+        );
+    }
+    // the settings of the request are passed along instead of set globally, as other requests run concurrently
+    let settings = Settings {
+        normalization: normalization.unwrap_or(Normalization::Mean),
+        as_similarity: false,
+        ..Settings::current()
+    };
+    let mut results = Vec::new();
+    for_each_osa_distance_in(
+        pool,
+        &lines,
+        &lines,
+        window_pairs(lines.len(), window),
+        &settings,
+        |dr| results.push(dr),
+    );
+    sort_distance_results(
+        &mut results,
+        normalization.is_some(),
+        request.descending,
+        &settings,
+    );
+    if let Some(top) = request.top {
+        results.truncate(top);
+    }
+    let pairs: Vec<Pair> = results
+        .iter()
+        .map(|dr| Pair {
+            line_a: dr.line_a,
+            line_b: dr.line_b,
+            dist: dr.dldist,
+            norm: dr.normalized_dldist,
+        })
+        .collect();
+    Response::ok(serde_json::json!({ "pairs": pairs }))
+}
+
+/// Reads a single request from the connection and writes the response. Connections are closed after each response.
+fn handle_connection(stream: TcpStream, pool: &ThreadPool) -> io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    // the head is read through a limit, so that endless lines do not exhaust the memory
+    let mut reader = BufReader::new((&stream).take(MAX_HEAD_LEN));
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line)? == 0 {
+        // closed without a request
+        return Ok(());
+    }
+    let mut content_len = 0usize;
+    let mut head_complete = request_line.ends_with('\n');
+    while head_complete {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        head_complete = header.ends_with('\n');
+        if header.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_len = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    if !head_complete {
+        let response = Response::error(
+            "431 Request Header Fields Too Large",
+            "request line and headers are too large",
+        );
+        return write_response(&stream, &response);
+    }
+    reader.get_mut().set_limit(MAX_BODY_LEN as u64);
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let response = match (method, path) {
+        ("GET", "/distance") => distance(query),
+        ("POST", "/pairwise") if content_len > MAX_BODY_LEN => {
+            Response::error("413 Payload Too Large", "request body is too large")
+        }
+        ("POST", "/pairwise") => {
+            let mut body = vec![0u8; content_len];
+            reader.read_exact(&mut body)?;
+            pairwise(&body, pool)
+        }
+        (_, "/distance" | "/pairwise") => {
+            Response::error("405 Method Not Allowed", "method not allowed")
+        }
+        _ => Response::error("404 Not Found", "unknown endpoint"),
+    };
+
+    write_response(&stream, &response)
+}
+
+fn write_response(mut stream: &TcpStream, response: &Response) -> io::Result<()> {
+    let body = response.body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+pub fn run(args: &ServeArguments) {
+    let thread_num = args.thread_num.map_or_else(
+        || {
+            thread::available_parallelism()
+                .map(|n| n.get().saturating_sub(1).max(1))
+                .unwrap_or(1)
+        },
+        NonZero::get,
+    );
+    if simd::supported() {
+        *KERNEL.lock().unwrap() = Kernel::Simd;
+    }
+
+    let listener = match TcpListener::bind((args.bind.as_str(), args.port)) {
+        Ok(listener) => listener,
+        Err(error) => panic!("Failed to listen on {}:{}: {error:?}", args.bind, args.port),
+    };
+    println!(
+        "==> Listening on http://{}:{} with {} threads...",
+        args.bind, args.port, thread_num
+    );
+    let pool = ThreadPool::new(thread_num);
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                println!("WARN: Failed to accept connection: {error:?}");
+                continue;
+            }
+        };
+        let over_limit = connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS;
+        let slot = ConnectionSlot(Arc::clone(&connections));
+        if over_limit {
+            drop(slot);
+            let response = Response::error("503 Service Unavailable", "too many connections");
+            let answered = stream
+                .set_write_timeout(Some(Duration::from_secs(1)))
+                .and_then(|_| write_response(&stream, &response));
+            if let Err(error) = answered {
+                println!("WARN: Failed to reject connection: {error:?}");
+            }
+            continue;
+        }
+        let pool = pool.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(error) = handle_connection(stream, &pool) {
+                println!("WARN: Failed to answer request: {error:?}");
+            }
+        });
+    }
+}
@@ -0,0 +1,147 @@
+//! SIMD kernel for the OSA distance.
+//!
+//! The cells of an anti-diagonal of the DP matrix only depend on the previous anti-diagonals, so they can be
+//! calculated independently of each other. The loop over an anti-diagonal is plain Rust without explicit intrinsics,
+//! written in a way the compiler auto-vectorizes, and it is compiled for AVX2 and SSE4.1 separately, one of which is picked at runtime depending on the
+//! features of the CPU.
+
+/// Padding in front of the strings, never equal to a byte nor to each other.
+const A_SENTINEL: u16 = 0x100;
+const B_SENTINEL: u16 = 0x101;
+
+/// Returns whether the CPU supports the instructions this kernel is compiled for.
+pub fn supported() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        is_x86_feature_detected!("avx2") || is_x86_feature_detected!("sse4.1")
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    {
+        false
+    }
+}
+
+/// Calculates the OSA distance with the best instruction set supported by the CPU.
+pub fn calculate_osa_distance(str_a: &[u8], str_b: &[u8]) -> u32 {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: the required CPU feature has just been checked
+            return unsafe { osa_antidiagonals_avx2(str_a, str_b) };
+        }
+        if is_x86_feature_detected!("sse4.1") {
+            // SAFETY: the required CPU feature has just been checked
+            return unsafe { osa_antidiagonals_sse41(str_a, str_b) };
+        }
+    }
+    osa_antidiagonals(str_a, str_b)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+fn osa_antidiagonals_avx2(str_a: &[u8], str_b: &[u8]) -> u32 {
+    osa_antidiagonals(str_a, str_b)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse4.1")]
+fn osa_antidiagonals_sse41(str_a: &[u8], str_b: &[u8]) -> u32 {
+    osa_antidiagonals(str_a, str_b)
+}
+
+/// Calculates the DP matrix of [`crate::osa::calculate_osa_distance_between_two_strings`] anti-diagonal by anti-diagonal.
+/// Anti-diagonal `d` holds the cells `(i, d - i)` and is stored indexed by `i + 1`.
+#[inline(always)]
+fn osa_antidiagonals(str_a: &[u8], str_b: &[u8]) -> u32 {
+    let (n, m) = (str_a.len(), str_b.len());
+    if n == 0 || m == 0 {
+        return n.max(m) as u32;
+    }
+
+    // a_ext[i] is the character of row i, b_rev[m + i - d] the one of column d - i at anti-diagonal d
+    let a_ext: Vec<u16> = std::iter::once(A_SENTINEL)
+        .chain(str_a.iter().map(|&c| c as u16))
+        .collect();
+    let b_rev: Vec<u16> = str_b
+        .iter()
+        .rev()
+        .map(|&c| c as u16)
+        .chain(std::iter::once(B_SENTINEL))
+        .collect();
+
+    // the current and the four previous anti-diagonals, as transpositions reach back four of them
+    let mut diagonals: [Vec<u32>; 5] = std::array::from_fn(|_| vec![0u32; n + 2]);
+    for d in 1..=n + m {
+        let [cur, d1, d2, _, d4] = &mut diagonals;
+        if d <= m {
+            cur[1] = d as u32;
+        }
+        if d <= n {
+            cur[d + 1] = d as u32;
+        }
+
+        let lo = 1.max(d.saturating_sub(m));
+        let hi = n.min(d - 1);
+        if lo <= hi {
+            let len = hi - lo + 1;
+            let k = m + lo - d;
+            // equally long slices let the compiler drop the bounds checks and vectorize the loop
+            let a_i = &a_ext[lo..lo + len];
+            let a_prev = &a_ext[lo - 1..lo - 1 + len];
+            let b_j = &b_rev[k..k + len];
+            let b_prev = &b_rev[k + 1..k + 1 + len];
+            let deletion = &d1[lo..lo + len];
+            let insertion = &d1[lo + 1..lo + 1 + len];
+            let substitution = &d2[lo..lo + len];
+            let transposition = &d4[lo - 1..lo - 1 + len];
+            let cells = &mut cur[lo + 1..lo + 1 + len];
+            for x in 0..len {
+                let cost = (a_i[x] != b_j[x]) as u32;
+                let mut dist = (deletion[x] + 1)
+                    .min(insertion[x] + 1)
+                    .min(substitution[x] + cost);
+                let transposed = (a_i[x] == b_prev[x]) & (a_prev[x] == b_j[x]);
+                let transposition_dist = transposition[x] + 1;
+                if transposed && transposition_dist < dist {
+                    dist = transposition_dist;
+                }
+                cells[x] = dist;
+            }
+        }
+        diagonals.rotate_right(1);
+    }
+    // the last anti-diagonal has been rotated into the position of the previous one
+    diagonals[1][n + 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osa::calculate_osa_distance_between_two_strings;
+    use crate::osa::tests::sample_pairs;
+
+    #[test]
+    fn antidiagonals_equal_the_scalar_kernel() {
+        for (a, b) in sample_pairs() {
+            let expected = calculate_osa_distance_between_two_strings(&a, &b);
+            assert_eq!(osa_antidiagonals(&a, &b), expected);
+            assert_eq!(calculate_osa_distance(&a, &b), expected);
+        }
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[test]
+    fn every_instruction_set_equals_the_scalar_kernel() {
+        for (a, b) in sample_pairs() {
+            let expected = calculate_osa_distance_between_two_strings(&a, &b);
+            if is_x86_feature_detected!("avx2") {
+                // SAFETY: the required CPU feature has just been checked
+                assert_eq!(unsafe { osa_antidiagonals_avx2(&a, &b) }, expected);
+            }
+            if is_x86_feature_detected!("sse4.1") {
+                // SAFETY: the required CPU feature has just been checked
+                assert_eq!(unsafe { osa_antidiagonals_sse41(&a, &b) }, expected);
+            }
+        }
+    }
+}
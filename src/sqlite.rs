@@ -0,0 +1,71 @@
+//! Result sink writing every calculated pair into a SQLite database, for querying the full pair set afterwards.
+
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use dldist::engine::DistanceResult;
+
+/// Amount of inserted pairs after which the transaction is committed.
+const COMMIT_INTERVAL: usize = 100_000;
+
+pub struct SqliteSink {
+    connection: Connection,
+    pending_cnt: usize,
+}
+
+impl SqliteSink {
+    /// Opens the database and (re)creates the `lines` and `pairs` tables. The lines are stored with their 1-based
+    /// position among the compared lines as `id`, which is also how the pairs refer to them, and with their `label`
+    /// as printed in the results, e.g. their line number in the input file.
+    pub fn create(path: &Path, labels: &[String], lines: &[Vec<u8>]) -> rusqlite::Result<Self> {
+        let mut connection = Connection::open(path)?;
+        connection.execute_batch(
+            "DROP TABLE IF EXISTS pairs;
+             DROP TABLE IF EXISTS lines;
+             CREATE TABLE lines (id INTEGER PRIMARY KEY, label TEXT NOT NULL, text TEXT NOT NULL);
+             CREATE TABLE pairs (line_a INTEGER NOT NULL, line_b INTEGER NOT NULL, dist INTEGER NOT NULL, norm REAL NOT NULL);",
+        )?;
+        let tx = connection.transaction()?;
+        {
+            let mut stmt = tx.prepare("INSERT INTO lines (id, label, text) VALUES (?1, ?2, ?3)")?;
+            for (i, (label, line)) in labels.iter().zip(lines).enumerate() {
+                stmt.execute(params![i as i64 + 1, label, String::from_utf8_lossy(line)])?;
+            }
+        }
+        tx.commit()?;
+        connection.execute_batch("BEGIN")?;
+        Ok(SqliteSink {
+            connection,
+            pending_cnt: 0,
+        })
+    }
+
+    pub fn insert(&mut self, dr: &DistanceResult) -> rusqlite::Result<()> {
+        self.connection
+            .prepare_cached(
+                "INSERT INTO pairs (line_a, line_b, dist, norm) VALUES (?1, ?2, ?3, ?4)",
+            )?
+            .execute(params![
+                dr.line_a as i64 + 1,
+                dr.line_b as i64 + 1,
+                dr.dldist,
+                dr.normalized_dldist
+            ])?;
+        self.pending_cnt += 1;
+        if self.pending_cnt >= COMMIT_INTERVAL {
+            self.connection.execute_batch("COMMIT; BEGIN")?;
+            self.pending_cnt = 0;
+        }
+        Ok(())
+    }
+
+    /// Commits the remaining pairs and adds indices for the typical lookups.
+    pub fn finish(self) -> rusqlite::Result<()> {
+        self.connection.execute_batch(
+            "COMMIT;
+             CREATE INDEX pairs_line_a ON pairs (line_a);
+             CREATE INDEX pairs_line_b ON pairs (line_b);",
+        )
+    }
+}
@@ -0,0 +1,213 @@
+//! Persisted distances of earlier runs, so that a run against an updated input only calculates the pairs involving
+//! new or changed lines.
+//!
+//! A store file starts with a header naming the settings the distances depend on, followed by the FNV-1a hashes of
+//! the compared lines and the distances of their pairs, which refer to the lines by their position in the list:
+//! ```text
+//! dldist-store v1 <settings>
+//! <hash of line 0>
+//! ...
+//! pairs
+//! <line_a> <line_b> <dldist>
+//! ...
+//! ```
+//! Since pairs are identified by the contents of their lines, they keep matching when lines are moved.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+
+use dldist::engine::{DistanceResult, Settings};
+
+use crate::ignore::content_hash;
+
+const HEADER_PREFIX: &str = "dldist-store v1";
+const PAIRS_MARKER: &str = "pairs";
+
+/// Distances of earlier runs, keyed by the ordered hashes of both lines of a pair.
+#[derive(Default)]
+pub struct Store {
+    distances: HashMap<(u64, u64), u32>,
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn pair_key(hash_a: u64, hash_b: u64) -> (u64, u64) {
+    (hash_a.min(hash_b), hash_a.max(hash_b))
+}
+
+impl Store {
+    /// Reads in a store file, which has to have been written with the same settings.
+    pub fn read(path: &Path, settings: &str) -> io::Result<Self> {
+        let mut file_lines = io::BufReader::new(File::open(path)?).lines();
+        let header = file_lines.next().transpose()?.unwrap_or_default();
+        if header != format!("{} {}", HEADER_PREFIX, settings) {
+            return Err(invalid_data(format!(
+                "store has been written with different settings: '{}'",
+                header
+            )));
+        }
+
+        let mut hashes = Vec::new();
+        for file_line in file_lines.by_ref() {
+            let file_line = file_line?;
+            if file_line == PAIRS_MARKER {
+                break;
+            }
+            match u64::from_str_radix(&file_line, 16) {
+                Ok(hash) => hashes.push(hash),
+                Err(_) => return Err(invalid_data(format!("invalid line hash: {}", file_line))),
+            }
+        }
+        let mut distances = HashMap::new();
+        for file_line in file_lines {
+            let file_line = file_line?;
+            let values: Vec<usize> = file_line
+                .split(' ')
+                .map(|v| v.parse::<usize>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid_data(format!("invalid pair in store: {}", file_line)))?;
+            match values[..] {
+                [la, lb, dldist] if la < hashes.len() && lb < hashes.len() => {
+                    distances.insert(pair_key(hashes[la], hashes[lb]), dldist as u32);
+                }
+                _ => {
+                    return Err(invalid_data(format!(
+                        "invalid pair in store: {}",
+                        file_line
+                    )))
+                }
+            }
+        }
+        Ok(Store { distances })
+    }
+
+    /// Calculates the distances of the given pairs, taking those of pairs with unchanged lines from the store.
+    /// Returns the results along with the amount of reused ones.
+    pub fn calculate_osa_distances<I>(
+        &self,
+        lines: &[Vec<u8>],
+        pairs: I,
+    ) -> (Vec<DistanceResult>, usize)
+    where
+        I: Iterator<Item = (usize, usize)>,
+    {
+        let hashes: Vec<u64> = lines.iter().map(|l| content_hash(l)).collect();
+        let settings = Settings::current();
+        let mut reused = Vec::new();
+        // the pairs are taken lazily, so that a deadline of the pairs iterator also stops the reused ones
+        let missing_pairs =
+            pairs.filter(
+                |&(la, lb)| match self.distances.get(&pair_key(hashes[la], hashes[lb])) {
+                    Some(&dldist) => {
+                        reused.push(DistanceResult::new(
+                            la as u64,
+                            lb as u64,
+                            lines[la].len(),
+                            lines[lb].len(),
+                            dldist,
+                            &settings,
+                        ));
+                        false
+                    }
+                    None => true,
+                },
+            );
+        let calculated = dldist::engine::calculate_osa_distances(lines, lines, missing_pairs);
+        let reused_cnt = reused.len();
+        let mut results = reused;
+        results.extend(calculated);
+        (results, reused_cnt)
+    }
+}
+
+/// Replaces the store file with the distances of the current run. Pairs of earlier runs whose lines are gone are
+/// dropped. The file is written next to the old one and renamed, so that an aborted write keeps the old store.
+pub fn write(
+    path: &Path,
+    settings: &str,
+    lines: &[Vec<u8>],
+    results: &[DistanceResult],
+) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+    writeln!(writer, "{} {}", HEADER_PREFIX, settings)?;
+    for line in lines {
+        writeln!(writer, "{:016x}", content_hash(line))?;
+    }
+    writeln!(writer, "{}", PAIRS_MARKER)?;
+    for dr in results {
+        writeln!(writer, "{} {} {}", dr.line_a, dr.line_b, dr.dldist)?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_data()?;
+    fs::rename(tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dldist::engine::window_pairs;
+
+    fn to_lines(texts: &[&str]) -> Vec<Vec<u8>> {
+        texts.iter().map(|text| text.as_bytes().to_vec()).collect()
+    }
+
+    fn sorted_distances(results: &[DistanceResult]) -> Vec<(u64, u64, u32)> {
+        let mut distances: Vec<_> = results
+            .iter()
+            .map(|dr| (dr.line_a, dr.line_b, dr.dldist))
+            .collect();
+        distances.sort_unstable();
+        distances
+    }
+
+    #[test]
+    fn stored_distances_are_reused_for_moved_lines() {
+        let path = std::env::temp_dir().join(format!("dldist-store-{}", std::process::id()));
+        let old_lines = to_lines(&["alpha", "beta", "gamma"]);
+        let (old_results, reused_cnt) =
+            Store::default().calculate_osa_distances(&old_lines, window_pairs(3, 3));
+        assert_eq!(reused_cnt, 0);
+        write(&path, "algorithm=osa", &old_lines, &old_results).unwrap();
+
+        // alpha and beta swapped, gamma replaced by delta
+        let lines = to_lines(&["beta", "alpha", "delta"]);
+        let store = Store::read(&path, "algorithm=osa").unwrap();
+        let (results, reused_cnt) = store.calculate_osa_distances(&lines, window_pairs(3, 3));
+        assert_eq!(reused_cnt, 1);
+        let expected = dldist::engine::calculate_osa_distances(&lines, &lines, window_pairs(3, 3));
+        assert_eq!(sorted_distances(&results), sorted_distances(&expected));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn distances_are_taken_from_the_store() {
+        let path = std::env::temp_dir().join(format!("dldist-store-taken-{}", std::process::id()));
+        let lines = to_lines(&["alpha", "beta"]);
+        fs::write(
+            &path,
+            format!(
+                "{} algorithm=osa\n{:016x}\n{:016x}\npairs\n0 1 42\n",
+                HEADER_PREFIX,
+                content_hash(b"alpha"),
+                content_hash(b"beta")
+            ),
+        )
+        .unwrap();
+        let store = Store::read(&path, "algorithm=osa").unwrap();
+        let (results, reused_cnt) = store.calculate_osa_distances(&lines, window_pairs(2, 2));
+        assert_eq!(reused_cnt, 1);
+        assert_eq!(sorted_distances(&results), [(0, 1, 42)]);
+
+        let read = Store::read(&path, "algorithm=token-set");
+        assert!(read.is_err_and(|error| error.kind() == io::ErrorKind::InvalidData));
+        fs::remove_file(&path).unwrap();
+    }
+}
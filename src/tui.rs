@@ -0,0 +1,315 @@
+//! Interactive terminal browser of the ranked pairs.
+//!
+//! The pairs can be scrolled through, expanded to a highlighted diff of their lines and filtered by a threshold
+//! which is adjusted on the fly. Marked pairs are exported in the format of `--ignore-pairs`.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use dldist::engine::{DistanceResult, Settings};
+
+use crate::diff::{diff_lines, SegmentKind};
+
+const HELP: &str =
+    "↑/↓ scroll  Enter diff  Space mark  +/- threshold  x clear threshold  w export marked  q quit";
+
+/// Settings of the browser taken from the command line.
+pub struct Options<'a> {
+    pub normalize: bool,
+    /// Whether the normalized distances are similarities in percent.
+    pub as_similarity: bool,
+    pub precision: usize,
+    pub threshold: Option<f32>,
+    pub export_path: &'a Path,
+}
+
+struct Browser<'a> {
+    results: &'a [DistanceResult],
+    labels: &'a [String],
+    lines: &'a [Vec<u8>],
+    options: Options<'a>,
+    threshold: Option<f32>,
+    /// Indices of the results passing the threshold, in the order of the ranking.
+    visible: Vec<usize>,
+    selected: usize,
+    offset: usize,
+    marked: BTreeSet<usize>,
+    expanded: bool,
+    status: String,
+}
+
+impl<'a> Browser<'a> {
+    fn filter(&mut self) {
+        let (normalize, threshold) = (self.options.normalize, self.threshold);
+        let settings = Settings::current();
+        self.visible = (0..self.results.len())
+            .filter(|&i| {
+                threshold.is_none_or(|t| self.results[i].is_similar(normalize, t, &settings))
+            })
+            .collect();
+        self.selected = self.selected.min(self.visible.len().saturating_sub(1));
+    }
+
+    fn adjust_threshold(&mut self, steps: f32) {
+        let step = match (self.options.normalize, self.options.as_similarity) {
+            (true, true) => 5.0,
+            (true, false) => 0.05,
+            (false, _) => 1.0,
+        };
+        // starting from the selected pair makes the first step remove or add the pairs right around it
+        let start = self.threshold.unwrap_or_else(|| {
+            self.visible
+                .get(self.selected)
+                .map_or(0.0, |&i| self.distance(&self.results[i]))
+        });
+        self.threshold = Some((start + steps * step).max(0.0));
+        self.filter();
+    }
+
+    fn distance(&self, dr: &DistanceResult) -> f32 {
+        if self.options.normalize {
+            dr.normalized_dldist
+        } else {
+            dr.dldist as f32
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let last = self.visible.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    fn export_marked(&self) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(self.options.export_path)?);
+        for &i in &self.marked {
+            let dr = &self.results[i];
+            writeln!(
+                writer,
+                "{},{}",
+                self.labels[dr.line_a as usize], self.labels[dr.line_b as usize]
+            )?;
+        }
+        writer.flush()
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let diff_height = if self.expanded { 6 } else { 0 };
+        let [header_area, table_area, diff_area, help_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(diff_height),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Paragraph::new(format!(
+                "{} of {} pairs{}, {} marked{}",
+                self.visible.len(),
+                self.results.len(),
+                match self.threshold {
+                    Some(t) => format!(" within threshold {:.*}", self.options.precision, t),
+                    None => String::new(),
+                },
+                self.marked.len(),
+                if self.status.is_empty() {
+                    String::new()
+                } else {
+                    format!(" | {}", self.status)
+                }
+            )),
+            header_area,
+        );
+        self.draw_table(frame, table_area);
+        if self.expanded {
+            self.draw_diff(frame, diff_area);
+        }
+        frame.render_widget(
+            Paragraph::new(HELP).style(Style::new().add_modifier(Modifier::DIM)),
+            help_area,
+        );
+    }
+
+    fn draw_table(&mut self, frame: &mut Frame, area: Rect) {
+        // only the rows on screen are built, as there may be millions of pairs
+        let height = area.height.saturating_sub(3).max(1) as usize;
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+        let rows = self
+            .visible
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(height)
+            .map(|(rank, &i)| {
+                let dr = &self.results[i];
+                let mut cells = vec![
+                    if self.marked.contains(&i) { "*" } else { " " }.to_string(),
+                    (rank + 1).to_string(),
+                    self.labels[dr.line_a as usize].clone(),
+                    self.labels[dr.line_b as usize].clone(),
+                    dr.dldist.to_string(),
+                ];
+                if self.options.normalize {
+                    cells.push(format!(
+                        "{:.*}",
+                        self.options.precision, dr.normalized_dldist
+                    ));
+                }
+                Row::new(cells)
+            });
+        let mut header = vec!["", "#", "Line A", "Line B", "Dist."];
+        let mut widths = vec![
+            Constraint::Length(1),
+            Constraint::Length(8),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Length(8),
+        ];
+        if self.options.normalize {
+            header.push(if self.options.as_similarity {
+                "Sim. %"
+            } else {
+                "Norm."
+            });
+            widths.push(Constraint::Length(self.options.precision as u16 + 4));
+        }
+        let table = Table::new(rows, widths)
+            .header(Row::new(header).style(Style::new().add_modifier(Modifier::BOLD)))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+            .block(Block::bordered().title(" Ranked pairs "));
+        let mut state = TableState::default()
+            .with_selected((!self.visible.is_empty()).then_some(self.selected - self.offset));
+        frame.render_stateful_widget(table, area, &mut state);
+    }
+
+    fn draw_diff(&self, frame: &mut Frame, area: Rect) {
+        let Some(&i) = self.visible.get(self.selected) else {
+            return;
+        };
+        let dr = &self.results[i];
+        let (la, lb) = (dr.line_a as usize, dr.line_b as usize);
+        let spans: Vec<Span> = diff_lines(&self.lines[la], &self.lines[lb])
+            .into_iter()
+            .map(|segment| match segment.kind {
+                SegmentKind::Equal => Span::raw(segment.text),
+                SegmentKind::Removed => Span::styled(
+                    segment.text,
+                    Style::new()
+                        .fg(Color::Red)
+                        .add_modifier(Modifier::CROSSED_OUT),
+                ),
+                SegmentKind::Added => Span::styled(
+                    segment.text,
+                    Style::new().fg(Color::Green).add_modifier(Modifier::BOLD),
+                ),
+            })
+            .collect();
+        let text = vec![
+            Line::from(format!(
+                "{}> {}",
+                self.labels[la],
+                String::from_utf8_lossy(&self.lines[la])
+            )),
+            Line::from(format!(
+                "{}> {}",
+                self.labels[lb],
+                String::from_utf8_lossy(&self.lines[lb])
+            )),
+            Line::from(spans),
+        ];
+        frame.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title(" Diff ")),
+            area,
+        );
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            self.status.clear();
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                KeyCode::PageUp => self.move_selection(-20),
+                KeyCode::PageDown => self.move_selection(20),
+                KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+                KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX),
+                KeyCode::Enter => self.expanded = !self.expanded,
+                KeyCode::Char(' ') => {
+                    if let Some(&i) = self.visible.get(self.selected) {
+                        if !self.marked.remove(&i) {
+                            self.marked.insert(i);
+                        }
+                        self.move_selection(1);
+                    }
+                }
+                KeyCode::Char('+') => self.adjust_threshold(1.0),
+                KeyCode::Char('-') => self.adjust_threshold(-1.0),
+                KeyCode::Char('x') => {
+                    self.threshold = None;
+                    self.filter();
+                }
+                KeyCode::Char('w') => {
+                    self.status = match self.export_marked() {
+                        Ok(()) => format!(
+                            "wrote {} pairs into '{}'",
+                            self.marked.len(),
+                            self.options.export_path.display()
+                        ),
+                        Err(error) => format!("failed to export: {error}"),
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Shows the results, which have to be sorted already, until the browser is quit.
+pub fn run(
+    results: &[DistanceResult],
+    labels: &[String],
+    lines: &[Vec<u8>],
+    options: Options,
+) -> io::Result<()> {
+    let mut browser = Browser {
+        results,
+        labels,
+        lines,
+        threshold: options.threshold,
+        options,
+        visible: Vec::new(),
+        selected: 0,
+        offset: 0,
+        marked: BTreeSet::new(),
+        expanded: false,
+        status: String::new(),
+    };
+    browser.filter();
+    let mut terminal = ratatui::init();
+    let result = browser.run(&mut terminal);
+    ratatui::restore();
+    result
+}
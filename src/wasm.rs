@@ -0,0 +1,64 @@
+//! wasm-bindgen wrapper of the distance and pairwise engine, built with
+//! `wasm-pack build --target web -- --features wasm`.
+//!
+//! ```js
+//! import init, { osa, pairwise } from "./pkg/dldist.js";
+//! await init();
+//! osa("abcd", "acbd"); // 1
+//! pairwise(text.split("\n").filter((l) => l), 0, 10); // [Pair { lineA: 0, lineB: 1, dist: 1, norm: 0.33 }, ...]
+//! ```
+
+use wasm_bindgen::prelude::*;
+
+use crate::engine::{
+    calculate_osa_distances_with, sort_distance_results, window_pairs, Normalization, Settings,
+};
+use crate::osa::{calculate_distance_with, DistanceSettings};
+
+/// Distance of a pair of lines, which are referred to by their 0-based indices.
+#[wasm_bindgen]
+pub struct Pair {
+    #[wasm_bindgen(js_name = lineA)]
+    pub line_a: u32,
+    #[wasm_bindgen(js_name = lineB)]
+    pub line_b: u32,
+    pub dist: u32,
+    /// Distance divided by the mean length of both lines.
+    pub norm: f32,
+}
+
+/// Returns the optimal string alignment distance of both strings, counted in bytes of their UTF-8 encoding.
+#[wasm_bindgen]
+pub fn osa(a: &str, b: &str) -> u32 {
+    calculate_distance_with(a.as_bytes(), b.as_bytes(), &DistanceSettings::default())
+}
+
+/// Calculates the distances of all pairs of lines, each line only paired with the `window` lines following it (0
+/// pairs all lines), and returns the `top` closest pairs (0 returns all of them).
+#[wasm_bindgen]
+pub fn pairwise(lines: Vec<String>, window: usize, top: usize) -> Vec<Pair> {
+    let lines: Vec<Vec<u8>> = lines.into_iter().map(String::into_bytes).collect();
+    let window = if window == 0 { lines.len() } else { window };
+    // the settings of the call are passed along instead of set globally, as the globals are shared by all calls
+    let settings = Settings {
+        thread_num: 1,
+        normalization: Normalization::Mean,
+        as_similarity: false,
+        distance: DistanceSettings::default(),
+    };
+    let mut results =
+        calculate_osa_distances_with(&lines, &lines, window_pairs(lines.len(), window), &settings);
+    sort_distance_results(&mut results, false, false, &settings);
+    if top > 0 {
+        results.truncate(top);
+    }
+    results
+        .iter()
+        .map(|dr| Pair {
+            line_a: dr.line_a as u32,
+            line_b: dr.line_b as u32,
+            dist: dr.dldist,
+            norm: dr.normalized_dldist,
+        })
+        .collect()
+}